@@ -1,22 +1,39 @@
-use std::{future::ready, pin::pin};
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::{ready, Future},
+    pin::pin,
+    sync::Arc,
+    time::Duration,
+};
 
 use futures_util::{
-    stream::{select, FuturesUnordered},
+    future::{select, Either},
+    stream::{self, FuturesUnordered},
     FutureExt, StreamExt,
 };
 use zbus::{
     dbus_proxy,
-    fdo::{InterfacesAdded, InterfacesRemoved},
-    zvariant::ObjectPath,
-    SignalStream,
+    fdo::{
+        DBusProxy, InterfacesAdded, InterfacesRemoved, InterfacesRemovedStream,
+        NameOwnerChangedStream, PropertiesChangedStream, PropertiesProxy,
+    },
+    zvariant::{ObjectPath, OwnedObjectPath, SerializeDict, Type, Value},
+    Message, SignalStream,
 };
 
 use crate::{
     address::{Address, AddressType},
-    device::{Changes, Device, PropertyName},
-    Error, Result, Session,
+    cache::is_below,
+    device::{Changes, Device, PropertyChange, PropertyName},
+    executor,
+    uuid::Uuid,
+    Error, ErrorKind, Result, Session,
 };
 
+/// How long [`Adapter::ensure_powered`] waits for the adapter to power on.
+const POWER_ON_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[dbus_proxy(
     interface = "org.bluez.Adapter1",
     default_service = "org.bluez",
@@ -25,6 +42,11 @@ use crate::{
 trait Adapter {
     async fn start_discovery(&self) -> zbus::Result<()>;
     async fn stop_discovery(&self) -> zbus::Result<()>;
+    async fn set_discovery_filter(&self, filter: &DiscoveryFilterDict) -> zbus::Result<()>;
+    async fn get_discovery_filters(&self) -> zbus::Result<Vec<String>>;
+    async fn remove_device(&self, device: &ObjectPath<'_>) -> zbus::Result<()>;
+    async fn connect_device(&self, properties: &ConnectDeviceDict)
+        -> zbus::Result<OwnedObjectPath>;
 
     #[dbus_proxy(property)]
     fn address(&self) -> zbus::Result<String>;
@@ -34,6 +56,82 @@ trait Adapter {
 
     #[dbus_proxy(property)]
     fn discovering(&self) -> zbus::Result<bool>;
+
+    #[dbus_proxy(property)]
+    fn name(&self) -> zbus::Result<String>;
+
+    #[dbus_proxy(property)]
+    fn alias(&self) -> zbus::Result<String>;
+    #[dbus_proxy(property)]
+    fn set_alias(&self, alias: &str) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn class(&self) -> zbus::Result<u32>;
+
+    #[dbus_proxy(property)]
+    fn powered(&self) -> zbus::Result<bool>;
+    #[dbus_proxy(property)]
+    fn set_powered(&self, powered: bool) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn power_state(&self) -> zbus::Result<String>;
+
+    #[dbus_proxy(property)]
+    fn discoverable(&self) -> zbus::Result<bool>;
+    #[dbus_proxy(property)]
+    fn set_discoverable(&self, discoverable: bool) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn discoverable_timeout(&self) -> zbus::Result<u32>;
+    #[dbus_proxy(property)]
+    fn set_discoverable_timeout(&self, timeout: u32) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn pairable(&self) -> zbus::Result<bool>;
+    #[dbus_proxy(property)]
+    fn set_pairable(&self, pairable: bool) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn pairable_timeout(&self) -> zbus::Result<u32>;
+    #[dbus_proxy(property)]
+    fn set_pairable_timeout(&self, timeout: u32) -> zbus::Result<()>;
+
+    #[dbus_proxy(property)]
+    fn connectable(&self) -> zbus::Result<bool>;
+    #[dbus_proxy(property)]
+    fn set_connectable(&self, connectable: bool) -> zbus::Result<()>;
+}
+
+#[derive(SerializeDict, Type)]
+#[zvariant(signature = "dict")]
+pub struct DiscoveryFilterDict {
+    // FIXME: `pub` because zbus' `dbus_proxy` macro *always* generates public proxy types and
+    // methods instead of copying the trait visibility
+    #[zvariant(rename = "UUIDs")]
+    uuids: Option<Vec<String>>,
+    #[zvariant(rename = "RSSI")]
+    rssi: Option<i16>,
+    #[zvariant(rename = "Pathloss")]
+    pathloss: Option<u16>,
+    #[zvariant(rename = "Transport")]
+    transport: Option<&'static str>,
+    #[zvariant(rename = "DuplicateData")]
+    duplicate_data: Option<bool>,
+    #[zvariant(rename = "Discoverable")]
+    discoverable: Option<bool>,
+    #[zvariant(rename = "Pattern")]
+    pattern: Option<String>,
+}
+
+#[derive(SerializeDict, Type)]
+#[zvariant(signature = "dict")]
+pub struct ConnectDeviceDict {
+    // FIXME: `pub` because zbus' `dbus_proxy` macro *always* generates public proxy types and
+    // methods instead of copying the trait visibility
+    #[zvariant(rename = "Address")]
+    address: String,
+    #[zvariant(rename = "AddressType")]
+    address_type: Option<&'static str>,
 }
 
 /// A BlueZ Bluetooth adapter.
@@ -47,6 +145,9 @@ impl Adapter {
     const PATH_PREFIX: &str = "/org/bluez/";
 
     /// Opens the system's default Bluetooth adapter.
+    ///
+    /// This is the adapter whose device name sorts first. On systems with multiple adapters, a
+    /// specific one can be opened with [`Adapter::open_by_name`] or [`Adapter::open_by_address`].
     pub async fn open(session: &Session) -> Result<Self> {
         let mut adapters = Self::enumerate(session).await?.collect::<Vec<_>>();
         adapters.sort_by(|a, b| a.name.cmp(&b.name));
@@ -58,6 +159,27 @@ impl Adapter {
         }
     }
 
+    /// Opens the Bluetooth adapter with the given device name (eg. `hci1`).
+    pub async fn open_by_name(session: &Session, name: &str) -> Result<Self> {
+        Self::enumerate(session)
+            .await?
+            .find(|adapter| adapter.name == name)
+            .ok_or_else(|| Error::from(format!("no such adapter: {}", name)))
+    }
+
+    /// Opens the Bluetooth adapter with the given device [`Address`].
+    pub async fn open_by_address(session: &Session, address: Address) -> Result<Self> {
+        for adapter in Self::enumerate(session).await? {
+            match adapter.address().await {
+                Ok(addr) if addr == address => return Ok(adapter),
+                Ok(_) => {}
+                Err(e) => log::warn!("failed to query address of {}: {}", adapter.name, e),
+            }
+        }
+
+        Err(Error::from(format!("no such adapter: {}", address)))
+    }
+
     /// Returns an iterator yielding all Bluetooth adapters on the system.
     pub async fn enumerate(session: &Session) -> Result<impl Iterator<Item = Self>> {
         log::debug!(
@@ -65,29 +187,21 @@ impl Adapter {
             session.conn.server_guid()
         );
 
-        let manager = session.object_manager().await?;
-        let objects = manager.get_managed_objects().await.map_err(Error::from)?;
+        let objects = session.cache.find("/", "org.bluez.Adapter1").await?;
         let mut hci_names = Vec::new();
-        for (obj_path, intfs) in &objects {
-            if intfs.contains_key("org.bluez.Adapter1") {
-                if let Some(name) = obj_path.strip_prefix(Self::PATH_PREFIX) {
-                    log::debug!("found BlueZ adapter at path {}", obj_path);
-                    hci_names.push(name.to_string());
-                } else {
-                    log::warn!("skipping adapter with unexpected path {}", obj_path);
-                }
+        for (obj_path, _) in &objects {
+            if let Some(name) = obj_path.strip_prefix(Self::PATH_PREFIX) {
+                log::debug!("found BlueZ adapter at path {}", obj_path);
+                hci_names.push(name.to_string());
+            } else {
+                log::warn!("skipping adapter with unexpected path {}", obj_path);
             }
         }
 
         let mut adapters = Vec::new();
         for name in hci_names {
-            let path = format!("{}{}", Self::PATH_PREFIX, name);
-            match AdapterProxy::new(&session.conn, path).await {
-                Ok(proxy) => adapters.push(Adapter {
-                    proxy,
-                    name,
-                    session: session.clone(),
-                }),
+            match Adapter::new(session, name.clone()).await {
+                Ok(adapter) => adapters.push(adapter),
                 Err(e) => log::error!("failed to open adapter {}: {}", name, e),
             }
         }
@@ -95,6 +209,44 @@ impl Adapter {
         Ok(adapters.into_iter())
     }
 
+    async fn new(session: &Session, name: String) -> Result<Self> {
+        let path = format!("{}{}", Self::PATH_PREFIX, name);
+        let proxy = session
+            .proxy(AdapterProxy::builder(&session.conn), path)
+            .await?;
+        Ok(Adapter {
+            proxy,
+            name,
+            session: session.clone(),
+        })
+    }
+
+    /// Returns an [`AdapterStream`] that reports Bluetooth adapters being added to or removed from
+    /// the system.
+    ///
+    /// The [`AdapterStream`] will first yield all adapters that are currently present. Restarts of
+    /// the BlueZ daemon are reported as the removal and re-addition of all adapters.
+    pub async fn hotplug_stream(session: &Session) -> Result<AdapterStream> {
+        // Subscribe to the signals before enumerating, so that no change can be missed.
+        let manager = session.object_manager().await?;
+        let signals = manager.receive_all_signals().await.map_err(Error::from)?;
+        let dbus = DBusProxy::new(&session.conn).await.map_err(Error::from)?;
+        let owner_changes = dbus
+            .receive_name_owner_changed_with_args(&[(0, session.service.as_str())])
+            .await
+            .map_err(Error::from)?;
+
+        let mut stream = AdapterStream {
+            session: session.clone(),
+            signals,
+            owner_changes,
+            known: Vec::new(),
+            pending: VecDeque::new(),
+        };
+        stream.enumerate().await?;
+        Ok(stream)
+    }
+
     /// Returns the adapter's device name (eg. `hci0`).
     pub fn device_name(&self) -> &str {
         &self.name
@@ -102,16 +254,268 @@ impl Adapter {
 
     /// Returns the Bluetooth device [`Address`] of this [`Adapter`].
     pub async fn address(&self) -> Result<Address> {
-        let string = self.proxy.address().await.map_err(Error::from)?;
+        let string: String = self.session.property(self.proxy.inner(), "Address").await?;
         string.parse().map_err(Error::from)
     }
 
     /// Returns the type of device [`Address`] used by this [`Adapter`].
     pub async fn address_type(&self) -> Result<AddressType> {
-        let string = self.proxy.address_type().await.map_err(Error::from)?;
+        let string: String = self
+            .session
+            .property(self.proxy.inner(), "AddressType")
+            .await?;
         AddressType::from_str(&string)
     }
 
+    /// Returns the system name of this [`Adapter`] (usually the host name).
+    pub async fn name(&self) -> Result<String> {
+        self.session.property(self.proxy.inner(), "Name").await
+    }
+
+    /// Returns the user-friendly name of this [`Adapter`] that is shown to remote devices.
+    ///
+    /// Unless changed via [`Adapter::set_alias`], this is the same as [`Adapter::name`].
+    pub async fn alias(&self) -> Result<String> {
+        self.session.property(self.proxy.inner(), "Alias").await
+    }
+
+    /// Sets the user-friendly name of this [`Adapter`].
+    ///
+    /// Setting an empty alias resets it to the value of [`Adapter::name`].
+    pub async fn set_alias(&self, alias: &str) -> Result<()> {
+        self.proxy.set_alias(alias).await.map_err(Error::from)
+    }
+
+    /// Returns the Bluetooth Class of Device of this [`Adapter`].
+    pub async fn class(&self) -> Result<u32> {
+        self.session.property(self.proxy.inner(), "Class").await
+    }
+
+    /// Returns whether this [`Adapter`] is powered on.
+    pub async fn is_powered(&self) -> Result<bool> {
+        self.session.property(self.proxy.inner(), "Powered").await
+    }
+
+    /// Powers this [`Adapter`] on or off.
+    ///
+    /// Note that the power state may take a while to change. Use [`Adapter::ensure_powered`] to
+    /// power on the adapter and wait until it is ready to use.
+    pub async fn set_powered(&self, powered: bool) -> Result<()> {
+        self.proxy.set_powered(powered).await.map_err(Error::from)
+    }
+
+    /// Returns the [`PowerState`] of this [`Adapter`].
+    ///
+    /// Unlike [`Adapter::is_powered`], this also reports whether the adapter is in the middle of
+    /// being powered on or off.
+    pub async fn power_state(&self) -> Result<PowerState> {
+        let string: String = self
+            .session
+            .property(self.proxy.inner(), "PowerState")
+            .await?;
+        PowerState::from_str(&string)
+    }
+
+    /// Powers on this [`Adapter`] and waits until it is ready to use.
+    ///
+    /// Returns an error if the adapter is blocked (eg. via `rfkill`) and cannot be powered on, if it
+    /// turns off again while being powered on, or if it does not become ready within 10 seconds.
+    pub async fn ensure_powered(&self) -> Result<()> {
+        match executor::timeout(POWER_ON_TIMEOUT, self.ensure_powered_impl()).await {
+            Some(res) => res,
+            None => Err(Error::timeout("adapter to power on")),
+        }
+    }
+
+    async fn ensure_powered_impl(&self) -> Result<()> {
+        // Wait for the proxy's property cache to be populated first, otherwise populating it would
+        // be reported as a change.
+        let _ = self.proxy.power_state().await;
+        let mut changes = self.proxy.receive_power_state_changed().await;
+        let mut requested = false;
+        if !self.is_powered().await? {
+            log::debug!("powering on {}", self.name);
+            self.set_powered(true).await?;
+            requested = true;
+        }
+
+        let mut state = match self.power_state().await {
+            Ok(state) => state,
+            Err(e) => {
+                // `PowerState` was added in BlueZ 5.64, fall back to `Powered` on older versions.
+                log::debug!("failed to query power state of {}: {}", self.name, e);
+                return match self.is_powered().await? {
+                    true => Ok(()),
+                    false => Err(Error::from("failed to power on adapter")),
+                };
+            }
+        };
+        loop {
+            match state {
+                PowerState::On => return Ok(()),
+                PowerState::Blocked => return Err(Error::from("adapter is blocked")),
+                // Powering on failed, or something else powered the adapter off again.
+                PowerState::Off if requested => {
+                    return Err(Error::from("adapter turned off while powering on"));
+                }
+                // The adapter was powered on, but is being turned off.
+                PowerState::Off => {
+                    log::debug!("powering on {}", self.name);
+                    self.set_powered(true).await?;
+                    requested = true;
+                }
+                PowerState::TurningOn | PowerState::TurningOff => {
+                    log::debug!("waiting for {} to power on ({:?})", self.name, state);
+                }
+            }
+
+            match changes.next().await {
+                Some(change) => {
+                    state = PowerState::from_str(&change.get().await.map_err(Error::from)?)?
+                }
+                None => return Err(Error::from("failed to power on adapter")),
+            }
+        }
+    }
+
+    /// Returns whether this [`Adapter`] is visible to other devices performing discovery.
+    pub async fn is_discoverable(&self) -> Result<bool> {
+        self.session
+            .property(self.proxy.inner(), "Discoverable")
+            .await
+    }
+
+    /// Makes this [`Adapter`] visible or invisible to other devices performing discovery.
+    ///
+    /// The adapter will stop being discoverable once the [discoverable timeout] expires.
+    ///
+    /// [discoverable timeout]: Adapter::set_discoverable_timeout
+    pub async fn set_discoverable(&self, discoverable: bool) -> Result<()> {
+        self.proxy
+            .set_discoverable(discoverable)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Returns how long this [`Adapter`] stays discoverable after being made discoverable.
+    ///
+    /// [`None`] means that the adapter stays discoverable indefinitely.
+    pub async fn discoverable_timeout(&self) -> Result<Option<Duration>> {
+        let secs: u32 = self
+            .session
+            .property(self.proxy.inner(), "DiscoverableTimeout")
+            .await?;
+        Ok(timeout_from_secs(secs))
+    }
+
+    /// Sets how long this [`Adapter`] stays discoverable after being made discoverable.
+    ///
+    /// [`None`] disables the timeout. The timeout has a resolution of 1 second.
+    pub async fn set_discoverable_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.proxy
+            .set_discoverable_timeout(timeout_to_secs(timeout))
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Returns whether this [`Adapter`] accepts incoming pairing requests.
+    pub async fn is_pairable(&self) -> Result<bool> {
+        self.session.property(self.proxy.inner(), "Pairable").await
+    }
+
+    /// Sets whether this [`Adapter`] accepts incoming pairing requests.
+    ///
+    /// The adapter will stop being pairable once the [pairable timeout] expires.
+    ///
+    /// [pairable timeout]: Adapter::set_pairable_timeout
+    pub async fn set_pairable(&self, pairable: bool) -> Result<()> {
+        self.proxy.set_pairable(pairable).await.map_err(Error::from)
+    }
+
+    /// Returns how long this [`Adapter`] stays pairable after being made pairable.
+    ///
+    /// [`None`] means that the adapter stays pairable indefinitely.
+    pub async fn pairable_timeout(&self) -> Result<Option<Duration>> {
+        let secs: u32 = self
+            .session
+            .property(self.proxy.inner(), "PairableTimeout")
+            .await?;
+        Ok(timeout_from_secs(secs))
+    }
+
+    /// Sets how long this [`Adapter`] stays pairable after being made pairable.
+    ///
+    /// [`None`] disables the timeout. The timeout has a resolution of 1 second.
+    pub async fn set_pairable_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.proxy
+            .set_pairable_timeout(timeout_to_secs(timeout))
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Returns whether this [`Adapter`] accepts incoming connections.
+    pub async fn is_connectable(&self) -> Result<bool> {
+        self.session
+            .property(self.proxy.inner(), "Connectable")
+            .await
+    }
+
+    /// Sets whether this [`Adapter`] accepts incoming connections.
+    ///
+    /// Making the adapter non-connectable also makes it non-discoverable.
+    pub async fn set_connectable(&self, connectable: bool) -> Result<()> {
+        self.proxy
+            .set_connectable(connectable)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Starts device discovery and returns a [`DiscoveryGuard`] that keeps it running.
+    ///
+    /// Discovery is stopped once the last [`DiscoveryGuard`] for this [`Adapter`] in the
+    /// [`Session`] is dropped or [stopped][DiscoveryGuard::stop]. This makes it safe for several
+    /// independent components to request discovery concurrently.
+    /// If another call is still starting discovery, this waits for it to finish instead of
+    /// returning early.
+    ///
+    /// Note that this mechanism does not coordinate with [`Adapter::start_discovery`] and
+    /// [`Adapter::stop_discovery`], which should not be mixed with it.
+    pub async fn discover(&self) -> Result<DiscoveryGuard> {
+        self.discover_impl(None).await
+    }
+
+    /// Like [`Adapter::discover`], but applies a [`DiscoveryFilter`] to the discovery.
+    ///
+    /// The [`DiscoveryFilter`] applies to all discovery performed by the [`Session`]. If multiple
+    /// [`DiscoveryGuard`]s with different filters exist, the most recently set filter wins.
+    pub async fn discover_with(&self, filter: &DiscoveryFilter) -> Result<DiscoveryGuard> {
+        self.discover_impl(Some(filter)).await
+    }
+
+    async fn discover_impl(&self, filter: Option<&DiscoveryFilter>) -> Result<DiscoveryGuard> {
+        if let Some(filter) = filter {
+            self.set_discovery_filter(filter).await?;
+        }
+
+        let start = async {
+            log::debug!("starting discovery on {}", self.name);
+            self.proxy.start_discovery().await.map_err(Error::from)
+        };
+        self.session
+            .discovery_refs
+            .acquire(
+                self.proxy.path().as_str(),
+                start,
+                stop_discovery(&self.proxy),
+            )
+            .await?;
+
+        Ok(DiscoveryGuard {
+            session: self.session.clone(),
+            proxy: Some(self.proxy.clone()),
+        })
+    }
+
     /// Starts the device discovery procedure.
     pub async fn start_discovery(&self) -> Result<()> {
         self.proxy.start_discovery().await.map_err(Error::from)
@@ -122,13 +526,139 @@ impl Adapter {
         self.proxy.stop_discovery().await.map_err(Error::from)
     }
 
+    /// Sets the [`DiscoveryFilter`] to apply to device discovery started by this process.
+    ///
+    /// The filter takes effect when discovery is (re)started via [`Adapter::start_discovery`].
+    /// Passing an empty [`DiscoveryFilter`] (as returned by [`DiscoveryFilter::new`]) removes any
+    /// previously set filter.
+    ///
+    /// Note that BlueZ merges the filters of all processes performing discovery, so devices that
+    /// don't match the filter may still be reported.
+    pub async fn set_discovery_filter(&self, filter: &DiscoveryFilter) -> Result<()> {
+        self.proxy
+            .set_discovery_filter(&filter.to_dict())
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Returns the names of the [`DiscoveryFilter`] fields supported by this [`Adapter`].
+    ///
+    /// The names are the ones used by BlueZ (eg. `UUIDs`, `RSSI`, `Transport`).
+    pub async fn discovery_filters(&self) -> Result<Vec<String>> {
+        self.proxy
+            .get_discovery_filters()
+            .await
+            .map_err(Error::from)
+    }
+
     /// Returns whether this [`Adapter`] is currently performing device discovery.
     ///
     /// Device discovery can be started by calling [`Adapter::start_discovery`]. Note that the value
     /// of [`Adapter::is_discovering`] may not immediately change to reflect that discovery has been
     /// requested.
     pub async fn is_discovering(&self) -> Result<bool> {
-        self.proxy.discovering().await.map_err(Error::from)
+        self.session
+            .property(self.proxy.inner(), "Discovering")
+            .await
+    }
+
+    /// Returns the [`Device`] with the given [`Address`], if it is known to this [`Adapter`].
+    ///
+    /// Known devices are those that have been paired, connected or discovered previously. This
+    /// does not perform device discovery.
+    pub async fn device(&self, address: Address) -> Result<Option<Device>> {
+        let objects = self
+            .session
+            .cache
+            .find(self.proxy.path().as_str(), "org.bluez.Device1")
+            .await?;
+        for (path, props) in objects {
+            let matches = match props.get("Address").map(|value| &**value) {
+                Some(Value::Str(s)) => s.parse::<Address>().is_ok_and(|addr| addr == address),
+                _ => false,
+            };
+            if matches {
+                return Device::new(self.session.clone(), path.into_inner())
+                    .await
+                    .map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Connects to the device with the given [`Address`] without performing device discovery.
+    ///
+    /// If the device is already known to this [`Adapter`], it is connected via
+    /// [`Device::connect`] instead.
+    ///
+    /// Note that BlueZ only offers the underlying `ConnectDevice` method when `bluetoothd` is run
+    /// with experimental features enabled (`-E`).
+    pub async fn connect_device(
+        &self,
+        address: Address,
+        address_type: AddressType,
+    ) -> Result<Device> {
+        let props = ConnectDeviceDict {
+            address: address.to_string(),
+            address_type: Some(address_type.as_str()),
+        };
+        match self.proxy.connect_device(&props).await.map_err(Error::from) {
+            Ok(path) => Device::new(self.session.clone(), path.into_inner()).await,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                log::debug!("{} is already known, connecting via Device1", address);
+                let device = self
+                    .device(address)
+                    .await?
+                    .ok_or_else(|| Error::from(format!("device {} disappeared", address)))?;
+                device.connect().await?;
+                Ok(device)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Removes a [`Device`] from this [`Adapter`], deleting any pairing and bonding information
+    /// stored about it.
+    ///
+    /// If the [`Device`] is connected, it will be disconnected first. Afterwards, the [`Device`]
+    /// object becomes invalid, and will only reappear when it is discovered again.
+    pub async fn remove_device(&self, device: &Device) -> Result<()> {
+        self.proxy
+            .remove_device(&device.path())
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Returns an [`AdapterChanges`] stream that reports changes to the state of this
+    /// [`Adapter`].
+    ///
+    /// The stream will return an error once the [`Adapter`] is removed from the system.
+    pub async fn change_stream(&self) -> Result<AdapterChanges> {
+        let proxy = self
+            .session
+            .proxy(
+                PropertiesProxy::builder(&self.session.conn),
+                self.proxy.path().to_owned(),
+            )
+            .await?;
+        let changes = proxy
+            .receive_properties_changed()
+            .await
+            .map_err(Error::from)?;
+        let removed = self
+            .session
+            .object_manager()
+            .await?
+            .receive_interfaces_removed()
+            .await
+            .map_err(Error::from)?;
+        Ok(AdapterChanges {
+            changes,
+            removed,
+            path: self.proxy.path().to_owned(),
+            change_buffer: Vec::new(),
+        })
     }
 
     /// Returns a [`DeviceStream`] that will yield all [`Device`]s known to this [`Adapter`].
@@ -137,7 +667,10 @@ impl Adapter {
     /// devices will also be yielded by the stream, even if those [`Device`]s aren't currently
     /// discoverable.
     pub async fn device_stream(&self) -> Result<DeviceStream> {
-        self.device_set().await?.into_device_stream().await
+        self.device_set([PropertyName::Alias, PropertyName::ServiceUuids])
+            .await?
+            .into_device_stream()
+            .await
     }
 
     /// Returns a [`DeviceSet`] containing all devices known to this [`Adapter`].
@@ -145,70 +678,482 @@ impl Adapter {
     /// If this [`Adapter`] is performing discovery, discovered devices will be added to the
     /// returned [`DeviceSet`] automatically. Otherwise, only "known" devices will be yielded by the
     /// [`DeviceSet`].
-    async fn device_set(&self) -> Result<DeviceSet> {
+    ///
+    /// Changes to the properties identified by the given [`PropertyName`]s will be reported by
+    /// [`DeviceSet::change`].
+    pub async fn device_set<I: IntoIterator<Item = PropertyName>>(
+        &self,
+        properties: I,
+    ) -> Result<DeviceSet> {
+        let interest = properties.into_iter().collect::<Vec<_>>();
         let manager = self.session.object_manager().await?;
         let signals = manager.receive_all_signals().await.map_err(Error::from)?;
 
         let mut devices = Vec::new();
         let mut changes = Vec::new();
-        let objects = manager.get_managed_objects().await.map_err(Error::from)?;
-        for (path, intfs) in objects {
-            if path.starts_with(self.proxy.path().as_str())
-                && intfs.contains_key("org.bluez.Device1")
-            {
-                let device = match Device::new(self.session.clone(), (*path).to_owned()).await {
-                    Ok(dev) => dev,
-                    Err(e) => {
-                        log::warn!("skipping device at {}: {}", path, e);
-                        continue;
-                    }
-                };
+        let objects = self
+            .session
+            .cache
+            .find(self.proxy.path().as_str(), "org.bluez.Device1")
+            .await?;
+        for (path, _) in objects {
+            let device = match Device::new(self.session.clone(), (*path).to_owned()).await {
+                Ok(dev) => dev,
+                Err(e) => {
+                    log::warn!("skipping device at {}: {}", path, e);
+                    continue;
+                }
+            };
 
-                let change = match device
-                    .property_change_stream([PropertyName::Alias, PropertyName::ServiceUuids])
-                    .await
-                {
-                    Ok(change) => change,
-                    Err(e) => {
-                        log::warn!(
-                            "failed to listen to property changes for {}: {} (skipping device)",
-                            path,
-                            e
-                        );
-                        continue;
-                    }
-                };
+            let change = match device.property_change_stream(interest.clone()).await {
+                Ok(change) => change,
+                Err(e) => {
+                    log::warn!(
+                        "failed to listen to property changes for {}: {} (skipping device)",
+                        path,
+                        e
+                    );
+                    continue;
+                }
+            };
 
-                devices.push(device);
-                changes.push(change);
-            }
+            devices.push(device);
+            changes.push(change);
         }
 
         Ok(DeviceSet {
             session: self.session.clone(),
             adapter_path: self.proxy.path().to_owned(),
             added_removed_stream: signals,
+            interest,
             devices,
             change_streams: changes,
         })
     }
 }
 
+fn timeout_from_secs(secs: u32) -> Option<Duration> {
+    match secs {
+        0 => None,
+        secs => Some(Duration::from_secs(secs.into())),
+    }
+}
+
+fn timeout_to_secs(timeout: Option<Duration>) -> u32 {
+    match timeout {
+        // BlueZ uses 0 to mean "no timeout", so round up to make sure a tiny timeout isn't
+        // interpreted as an infinite one.
+        Some(timeout) => timeout
+            .as_secs()
+            .saturating_add(u64::from(timeout.subsec_nanos() != 0))
+            .max(1)
+            .try_into()
+            .unwrap_or(u32::MAX),
+        None => 0,
+    }
+}
+
+/// The power state of an [`Adapter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PowerState {
+    /// The adapter is powered on.
+    On,
+    /// The adapter is powered off.
+    Off,
+    /// The adapter is in the process of powering on.
+    TurningOn,
+    /// The adapter is in the process of powering off.
+    TurningOff,
+    /// The adapter is powered off and blocked from being powered on (eg. via `rfkill`).
+    Blocked,
+}
+
+impl PowerState {
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "on" => Ok(Self::On),
+            "off" => Ok(Self::Off),
+            "off-enabling" => Ok(Self::TurningOn),
+            "on-disabling" => Ok(Self::TurningOff),
+            "off-blocked" => Ok(Self::Blocked),
+            _ => Err(Error::from(format!("invalid power state '{}'", s))),
+        }
+    }
+}
+
+impl fmt::Debug for Adapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Adapter").field("name", &self.name).finish()
+    }
+}
+
+/// A stream of Bluetooth adapters being added to or removed from the system.
+///
+/// Returned by [`Adapter::hotplug_stream`].
+pub struct AdapterStream {
+    session: Session,
+    signals: SignalStream<'static>,
+    owner_changes: NameOwnerChangedStream<'static>,
+    /// Device names of the adapters that have been reported as added, but not as removed.
+    known: Vec<String>,
+    pending: VecDeque<AdapterEvent>,
+}
+
+impl AdapterStream {
+    /// Asynchronously waits for the next [`AdapterEvent`].
+    ///
+    /// # Errors
+    ///
+    /// If this method returns an error, the caller should treat this as a permanent condition and
+    /// create a new [`AdapterStream`].
+    pub async fn next(&mut self) -> Result<AdapterEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(event);
+            }
+
+            match select(self.signals.next(), self.owner_changes.next()).await {
+                Either::Left((Some(message), _)) => self.handle_signal(message).await,
+                Either::Right((Some(changed), _)) => {
+                    let args = changed.args().map_err(Error::from)?;
+                    if args.new_owner().is_some() {
+                        log::debug!("BlueZ service started, reenumerating adapters");
+                        self.enumerate().await?;
+                    } else {
+                        log::debug!("BlueZ service stopped, removing all adapters");
+                        self.pending
+                            .extend(self.known.drain(..).map(AdapterEvent::Removed));
+                    }
+                }
+                Either::Left((None, _)) | Either::Right((None, _)) => {
+                    return Err(Error::from("adapter event stream ended"));
+                }
+            }
+        }
+    }
+
+    async fn handle_signal(&mut self, message: Arc<Message>) {
+        if let Some(added) = InterfacesAdded::from_message(message.clone()) {
+            let Ok(args) = added.args() else { return };
+            if !args
+                .interfaces_and_properties
+                .contains_key("org.bluez.Adapter1")
+            {
+                return;
+            }
+            if let Some(name) = args.object_path.strip_prefix(Adapter::PATH_PREFIX) {
+                self.add(name.to_string()).await;
+            }
+        } else if let Some(removed) = InterfacesRemoved::from_message(message) {
+            let Ok(args) = removed.args() else { return };
+            if !args.interfaces.contains(&"org.bluez.Adapter1") {
+                return;
+            }
+            if let Some(name) = args.object_path.strip_prefix(Adapter::PATH_PREFIX) {
+                if let Some(i) = self.known.iter().position(|known| known == name) {
+                    let name = self.known.remove(i);
+                    self.pending.push_back(AdapterEvent::Removed(name));
+                }
+            }
+        }
+    }
+
+    /// Queues [`AdapterEvent::Added`] for all present adapters that haven't been reported yet.
+    async fn enumerate(&mut self) -> Result<()> {
+        for adapter in Adapter::enumerate(&self.session).await? {
+            if !self.known.contains(&adapter.name) {
+                self.known.push(adapter.name.clone());
+                self.pending.push_back(AdapterEvent::Added(adapter));
+            }
+        }
+        Ok(())
+    }
+
+    async fn add(&mut self, name: String) {
+        if self.known.contains(&name) {
+            return;
+        }
+        match Adapter::new(&self.session, name.clone()).await {
+            Ok(adapter) => {
+                self.known.push(name);
+                self.pending.push_back(AdapterEvent::Added(adapter));
+            }
+            Err(e) => log::error!("failed to open adapter {}: {}", name, e),
+        }
+    }
+}
+
+/// An event reported by an [`AdapterStream`].
+#[derive(Debug)]
+pub enum AdapterEvent {
+    /// The given [`Adapter`] was added to the system.
+    Added(Adapter),
+    /// The adapter with the given device name (eg. `hci0`) was removed from the system.
+    Removed(String),
+}
+
+/// A stream of changes to the state of an [`Adapter`].
+///
+/// Returned by [`Adapter::change_stream`].
+pub struct AdapterChanges {
+    changes: PropertiesChangedStream<'static>,
+    removed: InterfacesRemovedStream<'static>,
+    path: ObjectPath<'static>,
+    change_buffer: Vec<AdapterChange>,
+}
+
+impl AdapterChanges {
+    /// Asynchronously waits until the state of the [`Adapter`] changes.
+    ///
+    /// Like [`Changes::wait`], multiple changes to a property may be collapsed into one.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error once the [`Adapter`] has been removed, if the underlying
+    /// notification stream ends, or if there is some other communication error. The caller should
+    /// assume that the stream is no longer operable if that happens.
+    pub async fn next(&mut self) -> Result<AdapterChange> {
+        loop {
+            if let Some(change) = self.change_buffer.pop() {
+                return Ok(change);
+            }
+
+            match select(self.changes.next(), self.removed.next()).await {
+                Either::Left((Some(changed), _)) => {
+                    let args = changed.args().map_err(Error::from)?;
+                    if args.interface_name != "org.bluez.Adapter1" {
+                        continue;
+                    }
+
+                    for (prop, value) in &args.changed_properties {
+                        if let Some(change) = AdapterChange::decode(prop, value) {
+                            self.change_buffer.push(change);
+                        }
+                    }
+                }
+                Either::Right((Some(removed), _)) => {
+                    let args = removed.args().map_err(Error::from)?;
+                    if args.object_path == self.path
+                        && args.interfaces.contains(&"org.bluez.Adapter1")
+                    {
+                        return Err(Error::from("adapter removed"));
+                    }
+                }
+                Either::Left((None, _)) | Either::Right((None, _)) => {
+                    return Err(Error::from("adapter change stream ended"));
+                }
+            }
+        }
+    }
+}
+
+/// A change to the state of an [`Adapter`], carrying the new value.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum AdapterChange {
+    /// [`Adapter::is_powered`] changed.
+    Powered(bool),
+    /// [`Adapter::power_state`] changed.
+    PowerState(PowerState),
+    /// [`Adapter::is_discovering`] changed.
+    Discovering(bool),
+    /// [`Adapter::is_discoverable`] changed.
+    Discoverable(bool),
+    /// [`Adapter::is_pairable`] changed.
+    Pairable(bool),
+    /// [`Adapter::alias`] changed.
+    Alias(String),
+}
+
+impl AdapterChange {
+    fn decode(name: &str, value: &Value<'_>) -> Option<Self> {
+        let change = match (name, value) {
+            ("Powered", Value::Bool(b)) => Self::Powered(*b),
+            ("PowerState", Value::Str(s)) => Self::PowerState(PowerState::from_str(s).ok()?),
+            ("Discovering", Value::Bool(b)) => Self::Discovering(*b),
+            ("Discoverable", Value::Bool(b)) => Self::Discoverable(*b),
+            ("Pairable", Value::Bool(b)) => Self::Pairable(*b),
+            ("Alias", Value::Str(s)) => Self::Alias(s.to_string()),
+            _ => {
+                log::trace!("ignoring adapter property change {}: {:?}", name, value);
+                return None;
+            }
+        };
+        Some(change)
+    }
+}
+
+/// Keeps device discovery on an [`Adapter`] running while alive.
+///
+/// Returned by [`Adapter::discover`] and [`Adapter::discover_with`].
+pub struct DiscoveryGuard {
+    session: Session,
+    /// [`None`] once the reference has been released.
+    proxy: Option<AdapterProxy<'static>>,
+}
+
+impl DiscoveryGuard {
+    /// Releases this [`DiscoveryGuard`], stopping discovery if it was the last one for its
+    /// [`Adapter`].
+    ///
+    /// Dropping a [`DiscoveryGuard`] has the same effect, but happens in the background and any
+    /// error is only logged. This method allows waiting for completion and observing errors.
+    pub async fn stop(mut self) -> Result<()> {
+        match self.proxy.take() {
+            Some(proxy) => {
+                let stop = stop_discovery(&proxy);
+                let refs = &self.session.discovery_refs;
+                refs.release(proxy.path().as_str(), stop).await
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for DiscoveryGuard {
+    fn drop(&mut self) {
+        if let Some(proxy) = self.proxy.take() {
+            let stop = stop_discovery(&proxy);
+            let refs = &self.session.discovery_refs;
+            refs.release_in_background(proxy.path().as_str(), stop);
+        }
+    }
+}
+
+fn stop_discovery(
+    proxy: &AdapterProxy<'static>,
+) -> impl Future<Output = Result<()>> + Send + 'static {
+    let proxy = proxy.clone();
+    async move {
+        log::debug!("stopping discovery on {}", proxy.path());
+        proxy.stop_discovery().await.map_err(Error::from)
+    }
+}
+
+/// The transport to use for device discovery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Transport {
+    /// Interleaved discovery of Bluetooth LE and BR/EDR (Classic) devices, depending on what the
+    /// [`Adapter`] supports.
+    Auto,
+    /// Only discover BR/EDR (Classic) devices.
+    BrEdr,
+    /// Only discover Bluetooth LE devices.
+    Le,
+}
+
+impl Transport {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::BrEdr => "bredr",
+            Self::Le => "le",
+        }
+    }
+}
+
+/// A filter restricting which [`Device`]s are reported by device discovery.
+///
+/// Passed to [`Adapter::set_discovery_filter`].
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryFilter {
+    uuids: Vec<Uuid>,
+    rssi: Option<i16>,
+    pathloss: Option<u16>,
+    transport: Option<Transport>,
+    duplicate_data: Option<bool>,
+    discoverable: Option<bool>,
+    pattern: Option<String>,
+}
+
+impl DiscoveryFilter {
+    /// Creates an empty [`DiscoveryFilter`] that doesn't filter anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only report [`Device`]s advertising at least one of the given service [`Uuid`]s.
+    pub fn uuids<I: IntoIterator<Item = Uuid>>(mut self, uuids: I) -> Self {
+        self.uuids.extend(uuids);
+        self
+    }
+
+    /// Only report [`Device`]s whose RSSI is at least `rssi` (in dBm).
+    ///
+    /// This cannot be combined with [`DiscoveryFilter::max_pathloss`].
+    pub fn min_rssi(mut self, rssi: i16) -> Self {
+        self.rssi = Some(rssi);
+        self
+    }
+
+    /// Only report [`Device`]s whose path loss is at most `pathloss` (in dB).
+    ///
+    /// This cannot be combined with [`DiscoveryFilter::min_rssi`].
+    pub fn max_pathloss(mut self, pathloss: u16) -> Self {
+        self.pathloss = Some(pathloss);
+        self
+    }
+
+    /// Sets the [`Transport`] to use for discovery.
+    pub fn transport(mut self, transport: Transport) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Sets whether to report every received advertisement, even if its data is unchanged.
+    ///
+    /// When disabled (BlueZ' default), only changes to the advertised data cause property changes.
+    pub fn duplicate_data(mut self, duplicate_data: bool) -> Self {
+        self.duplicate_data = Some(duplicate_data);
+        self
+    }
+
+    /// Sets whether to make the [`Adapter`] discoverable while discovering.
+    pub fn discoverable(mut self, discoverable: bool) -> Self {
+        self.discoverable = Some(discoverable);
+        self
+    }
+
+    /// Only report [`Device`]s whose address or name starts with `pattern`.
+    pub fn pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.pattern = Some(pattern.into());
+        self
+    }
+
+    fn to_dict(&self) -> DiscoveryFilterDict {
+        DiscoveryFilterDict {
+            uuids: (!self.uuids.is_empty())
+                .then(|| self.uuids.iter().map(ToString::to_string).collect()),
+            rssi: self.rssi,
+            pathloss: self.pathloss,
+            transport: self.transport.map(Transport::as_str),
+            duplicate_data: self.duplicate_data,
+            discoverable: self.discoverable,
+            pattern: self.pattern.clone(),
+        }
+    }
+}
+
 /// A set of [`Device`]s currently visible to an [`Adapter`].
 ///
 /// Returned by [`Adapter::device_set`].
-struct DeviceSet {
+pub struct DeviceSet {
     session: Session,
     adapter_path: ObjectPath<'static>,
     added_removed_stream: SignalStream<'static>,
+    interest: Vec<PropertyName>,
     change_streams: Vec<Changes>,
     devices: Vec<Device>,
 }
 
-// NB: `DeviceSet` is currently private because `DeviceStream` suffices for most things and I'm not
-// confident that its API is any good.
-
 impl DeviceSet {
+    /// Returns the [`Device`]s currently in this [`DeviceSet`].
+    pub fn devices(&self) -> &[Device] {
+        &self.devices
+    }
+
     /// Returns a [`DeviceStream`] that yields both all currently known [`Device`]s, as well as all
     /// devices discovered in the future.
     ///
@@ -228,10 +1173,13 @@ impl DeviceSet {
             .filter_map(|message| async {
                 if let Some(added) = InterfacesAdded::from_message(message.clone()) {
                     let args = added.args().ok()?;
-                    if args.object_path.starts_with(self.adapter_path.as_str())
+                    if is_below(&args.object_path, &self.adapter_path)
                         && args
                             .interfaces_and_properties
                             .contains_key("org.bluez.Device1")
+                        // Devices we already know about (eg. from the initial enumeration racing
+                        // with the signal) must not be reported twice.
+                        && !self.devices.iter().any(|dev| dev.path() == args.object_path)
                     {
                         let device = match Device::new(self.session.clone(), args.object_path.to_owned()).await {
                             Ok(dev) => dev,
@@ -241,7 +1189,7 @@ impl DeviceSet {
                             }
                         };
 
-                        let change = match device.property_change_stream([PropertyName::Alias, PropertyName::ServiceUuids]).await {
+                        let change = match device.property_change_stream(self.interest.iter().copied()).await {
                             Ok(change) => change,
                             Err(e) => {
                                 log::warn!(
@@ -259,7 +1207,7 @@ impl DeviceSet {
                     }
                 } else if let Some(removed) = InterfacesRemoved::from_message(message) {
                     let args = removed.args().ok()?;
-                    if args.object_path.starts_with(self.adapter_path.as_str())
+                    if is_below(&args.object_path, &self.adapter_path)
                         && args.interfaces.contains(&"org.bluez.Device1")
                     {
                         if let Some(i) = self.devices.iter().position(|dev| dev.path() == args.object_path) {
@@ -281,11 +1229,11 @@ impl DeviceSet {
             .enumerate()
             .map(|(i, change)| {
                 change
-                    .wait()
+                    .next_change()
                     .map(move |prop| prop.map(|prop| Modification::Change(i, prop)))
             })
             .collect();
-        let mut stream = pin!(select(
+        let mut stream = pin!(stream::select(
             added_removed_stream,
             dev.filter_map(|res| ready(res.ok()))
         ));
@@ -294,6 +1242,11 @@ impl DeviceSet {
     }
 
     /// Asynchronously waits for and applies a change to this [`DeviceSet`].
+    ///
+    /// # Errors
+    ///
+    /// If this method returns an error, the caller should treat this as a permanent condition. It
+    /// is likely that the [`Adapter`] has encountered a fatal error and needs to be reenumerated.
     pub async fn change(&mut self) -> Result<DeviceSetChange<'_>> {
         match self.next_modification().await {
             Some(Modification::Add(device, change)) => {
@@ -319,11 +1272,12 @@ impl DeviceSet {
 enum Modification {
     Add(Device, Changes),
     Remove(usize),
-    Change(usize, PropertyName),
+    Change(usize, PropertyChange),
 }
 
 /// Describes a change to a [`DeviceSet`], returned by [`DeviceSet::change`].
-enum DeviceSetChange<'a> {
+#[derive(Debug)]
+pub enum DeviceSetChange<'a> {
     /// The given [`Device`] was just added (discovered).
     Added(&'a Device),
     /// The given [`Device`] was removed (calling any methods on it will probably fail).
@@ -331,9 +1285,9 @@ enum DeviceSetChange<'a> {
     /// A property of the [`Device`] was changed (eg. the set of advertised services has been filled
     /// as part of device discovery, or the device's name was retrieved).
     ///
-    /// Note that the [`DeviceSet`] only listens to changes to [`PropertyName::Alias`] and
-    /// [`PropertyName::ServiceUuids`]. Any other property changes will not be reported.
-    Changed(&'a Device, PropertyName),
+    /// Note that the [`DeviceSet`] only listens to changes to the [`PropertyName`]s passed to
+    /// [`Adapter::device_set`]. Any other property changes will not be reported.
+    Changed(&'a Device, PropertyChange),
 }
 
 /// A stream that yields newly discovered or changed [`Device`]s.
@@ -33,6 +33,13 @@ impl AddressType {
             _ => Err(crate::Error::from(format!("invalid address type '{}'", s))),
         }
     }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::Random => "random",
+        }
+    }
 }
 
 /// A 6-Byte Bluetooth device address.
@@ -0,0 +1,555 @@
+//! Pairing agents.
+//!
+//! BlueZ delegates user interaction required during pairing (like entering or confirming a
+//! passkey) to an *agent*. Applications can provide their own agent by implementing the [`Agent`]
+//! trait and registering it via [`AgentHandle::register`].
+//!
+//! This module also provides two ready-made agents: [`FixedPasskey`] and [`AutoAccept`].
+
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use zbus::{
+    dbus_interface,
+    zvariant::{ObjectPath, OwnedObjectPath},
+    Connection,
+};
+
+use crate::{device::Device, executor, uuid::Uuid, Error, Result, Session};
+
+mod private {
+    use zbus::{dbus_proxy, zvariant::ObjectPath, DBusError};
+
+    #[dbus_proxy(
+        interface = "org.bluez.AgentManager1",
+        default_service = "org.bluez",
+        default_path = "/org/bluez",
+        assume_defaults = false
+    )]
+    trait AgentManager {
+        async fn register_agent(
+            &self,
+            agent: &ObjectPath<'_>,
+            capability: &str,
+        ) -> zbus::Result<()>;
+        async fn unregister_agent(&self, agent: &ObjectPath<'_>) -> zbus::Result<()>;
+        async fn request_default_agent(&self, agent: &ObjectPath<'_>) -> zbus::Result<()>;
+    }
+
+    /// Errors returned to BlueZ by the exported `org.bluez.Agent1` object.
+    #[derive(Debug, DBusError)]
+    #[dbus_error(prefix = "org.bluez.Error")]
+    pub(super) enum AgentReply {
+        #[dbus_error(zbus_error)]
+        ZBus(zbus::Error),
+        Rejected(String),
+        Canceled(String),
+    }
+}
+
+use private::{AgentManagerProxy, AgentReply};
+
+/// The input and output capabilities of an [`Agent`].
+///
+/// This determines which pairing method BlueZ will use, and thus which [`Agent`] methods will be
+/// called during pairing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Capability {
+    /// The agent can display a passkey, but cannot accept any input.
+    DisplayOnly,
+    /// The agent can display a passkey and ask the user to confirm it.
+    DisplayYesNo,
+    /// The agent can accept passkey input, but cannot display anything.
+    KeyboardOnly,
+    /// The agent can neither display anything nor accept input ("Just Works" pairing).
+    NoInputNoOutput,
+    /// The agent can display a passkey and accept passkey input.
+    KeyboardDisplay,
+}
+
+impl Capability {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::DisplayOnly => "DisplayOnly",
+            Self::DisplayYesNo => "DisplayYesNo",
+            Self::KeyboardOnly => "KeyboardOnly",
+            Self::NoInputNoOutput => "NoInputNoOutput",
+            Self::KeyboardDisplay => "KeyboardDisplay",
+        }
+    }
+}
+
+/// The error type returned by [`Agent`] methods to deny a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AgentError {
+    /// The request was rejected.
+    Rejected,
+    /// The request was canceled (eg. by the user).
+    Canceled,
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rejected => f.write_str("request rejected"),
+            Self::Canceled => f.write_str("request canceled"),
+        }
+    }
+}
+
+impl std::error::Error for AgentError {}
+
+impl From<AgentError> for AgentReply {
+    fn from(e: AgentError) -> Self {
+        match e {
+            AgentError::Rejected => Self::Rejected(e.to_string()),
+            AgentError::Canceled => Self::Canceled(e.to_string()),
+        }
+    }
+}
+
+/// Handles the user interaction required during pairing.
+///
+/// All methods have default implementations that reject the request (or do nothing, for methods
+/// that only display information), so implementors only need to override the methods relevant to
+/// their [`Capability`].
+///
+/// Passkeys are 6-digit numbers in the range `0..=999999`, and should be displayed zero-padded.
+pub trait Agent: Send + Sync + 'static {
+    /// Returns the input and output [`Capability`] of this agent.
+    ///
+    /// Defaults to [`Capability::KeyboardDisplay`].
+    fn capability(&self) -> Capability {
+        Capability::KeyboardDisplay
+    }
+
+    /// Called when BlueZ no longer uses this agent (eg. because the BlueZ service is stopping).
+    fn release(&self) {}
+
+    /// Requests the PIN code to use for legacy pairing with `device`.
+    ///
+    /// The PIN code consists of 1 to 16 alphanumeric characters.
+    fn request_pin_code(
+        &self,
+        device: &Device,
+    ) -> impl Future<Output = std::result::Result<String, AgentError>> + Send {
+        let _ = device;
+        async { Err(AgentError::Rejected) }
+    }
+
+    /// Requests that `pin_code` is displayed to the user, so that it can be entered on `device`.
+    fn display_pin_code(
+        &self,
+        device: &Device,
+        pin_code: &str,
+    ) -> impl Future<Output = std::result::Result<(), AgentError>> + Send {
+        let _ = (device, pin_code);
+        async { Ok(()) }
+    }
+
+    /// Requests the passkey to use for pairing with `device`.
+    fn request_passkey(
+        &self,
+        device: &Device,
+    ) -> impl Future<Output = std::result::Result<u32, AgentError>> + Send {
+        let _ = device;
+        async { Err(AgentError::Rejected) }
+    }
+
+    /// Requests that `passkey` is displayed to the user, so that it can be entered on `device`.
+    ///
+    /// `entered` is the number of digits the user has already entered on the remote side. This
+    /// method may be called multiple times while the user is typing.
+    fn display_passkey(
+        &self,
+        device: &Device,
+        passkey: u32,
+        entered: u16,
+    ) -> impl Future<Output = std::result::Result<(), AgentError>> + Send {
+        let _ = (device, passkey, entered);
+        async { Ok(()) }
+    }
+
+    /// Requests that the user confirms that `passkey` is also displayed by `device`.
+    fn request_confirmation(
+        &self,
+        device: &Device,
+        passkey: u32,
+    ) -> impl Future<Output = std::result::Result<(), AgentError>> + Send {
+        let _ = (device, passkey);
+        async { Err(AgentError::Rejected) }
+    }
+
+    /// Requests authorization for pairing with `device`, when no other interaction is needed
+    /// ("Just Works" pairing initiated by the remote device).
+    fn request_authorization(
+        &self,
+        device: &Device,
+    ) -> impl Future<Output = std::result::Result<(), AgentError>> + Send {
+        let _ = device;
+        async { Err(AgentError::Rejected) }
+    }
+
+    /// Requests authorization for `device` to connect to the local service identified by
+    /// `service`.
+    fn authorize_service(
+        &self,
+        device: &Device,
+        service: Uuid,
+    ) -> impl Future<Output = std::result::Result<(), AgentError>> + Send {
+        let _ = (device, service);
+        async { Err(AgentError::Rejected) }
+    }
+
+    /// Called when BlueZ cancels an outstanding request (eg. because pairing timed out).
+    fn cancel(&self) {}
+}
+
+/// The `org.bluez.Agent1` object exported on the [`Session`]'s D-Bus connection.
+struct AgentInterface<A> {
+    session: Session,
+    agent: A,
+}
+
+impl<A: Agent> AgentInterface<A> {
+    async fn device(&self, path: OwnedObjectPath) -> std::result::Result<Device, AgentReply> {
+        Device::new(self.session.clone(), path.into_inner())
+            .await
+            .map_err(|e| AgentReply::Rejected(e.to_string()))
+    }
+}
+
+#[dbus_interface(name = "org.bluez.Agent1")]
+impl<A: Agent> AgentInterface<A> {
+    async fn release(&self) {
+        log::debug!("agent released");
+        self.agent.release();
+    }
+
+    async fn request_pin_code(
+        &self,
+        device: OwnedObjectPath,
+    ) -> std::result::Result<String, AgentReply> {
+        let device = self.device(device).await?;
+        Ok(self.agent.request_pin_code(&device).await?)
+    }
+
+    async fn display_pin_code(
+        &self,
+        device: OwnedObjectPath,
+        pincode: String,
+    ) -> std::result::Result<(), AgentReply> {
+        let device = self.device(device).await?;
+        Ok(self.agent.display_pin_code(&device, &pincode).await?)
+    }
+
+    async fn request_passkey(
+        &self,
+        device: OwnedObjectPath,
+    ) -> std::result::Result<u32, AgentReply> {
+        let device = self.device(device).await?;
+        Ok(self.agent.request_passkey(&device).await?)
+    }
+
+    async fn display_passkey(
+        &self,
+        device: OwnedObjectPath,
+        passkey: u32,
+        entered: u16,
+    ) -> std::result::Result<(), AgentReply> {
+        let device = self.device(device).await?;
+        Ok(self
+            .agent
+            .display_passkey(&device, passkey, entered)
+            .await?)
+    }
+
+    async fn request_confirmation(
+        &self,
+        device: OwnedObjectPath,
+        passkey: u32,
+    ) -> std::result::Result<(), AgentReply> {
+        let device = self.device(device).await?;
+        Ok(self.agent.request_confirmation(&device, passkey).await?)
+    }
+
+    async fn request_authorization(
+        &self,
+        device: OwnedObjectPath,
+    ) -> std::result::Result<(), AgentReply> {
+        let device = self.device(device).await?;
+        Ok(self.agent.request_authorization(&device).await?)
+    }
+
+    async fn authorize_service(
+        &self,
+        device: OwnedObjectPath,
+        uuid: String,
+    ) -> std::result::Result<(), AgentReply> {
+        let device = self.device(device).await?;
+        let uuid = uuid
+            .parse()
+            .map_err(|e| AgentReply::Rejected(format!("invalid service UUID: {}", e)))?;
+        Ok(self.agent.authorize_service(&device, uuid).await?)
+    }
+
+    async fn cancel(&self) {
+        log::debug!("agent request canceled");
+        self.agent.cancel();
+    }
+}
+
+/// Options for registering an [`Agent`] via [`AgentHandle::register_with`].
+#[derive(Debug, Clone, Default)]
+pub struct AgentOptions {
+    default: bool,
+}
+
+impl AgentOptions {
+    /// Creates the default [`AgentOptions`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether to make the [`Agent`] the system's default agent.
+    ///
+    /// The default agent also handles pairing requests not initiated by this application (eg.
+    /// pairing requests coming from remote devices). This is off by default.
+    pub fn default_agent(mut self, default: bool) -> Self {
+        self.default = default;
+        self
+    }
+}
+
+/// A registered [`Agent`].
+///
+/// The [`Agent`] stays registered with BlueZ until this handle is dropped or
+/// [unregistered][AgentHandle::unregister].
+pub struct AgentHandle {
+    /// [`None`] once the agent has been unregistered.
+    inner: Option<Registration>,
+}
+
+struct Registration {
+    session: Session,
+    path: ObjectPath<'static>,
+    /// Whether the agent was successfully registered with the `AgentManager1`.
+    registered: bool,
+    /// Removes the type-erased [`AgentInterface`] from the object server.
+    remove: RemoveFn,
+}
+
+type RemoveFn = fn(Connection, ObjectPath<'static>) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+
+impl AgentHandle {
+    /// Registers `agent` with BlueZ.
+    ///
+    /// The [`Agent`] will be used for pairing attempts started by this application (eg. via
+    /// [`Device::pair`]).
+    pub async fn register<A: Agent>(session: &Session, agent: A) -> Result<Self> {
+        Self::register_with(session, agent, &AgentOptions::new()).await
+    }
+
+    /// Registers `agent` with BlueZ, using the given [`AgentOptions`].
+    pub async fn register_with<A: Agent>(
+        session: &Session,
+        agent: A,
+        options: &AgentOptions,
+    ) -> Result<Self> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path = ObjectPath::from_string_unchecked(format!("/blues/agent{}", id));
+        let capability = agent.capability();
+
+        let iface = AgentInterface {
+            session: session.clone(),
+            agent,
+        };
+        session
+            .conn
+            .object_server()
+            .at(&path, iface)
+            .await
+            .map_err(Error::from)?;
+
+        // From here on, dropping `handle` cleans up after us.
+        let mut handle = AgentHandle {
+            inner: Some(Registration {
+                session: session.clone(),
+                path: path.clone(),
+                registered: false,
+                remove: remove_interface::<A>,
+            }),
+        };
+
+        log::debug!(
+            "registering agent at {} with capability {:?}",
+            path,
+            capability
+        );
+        let manager = session
+            .proxy(AgentManagerProxy::builder(&session.conn), "/org/bluez")
+            .await?;
+        manager
+            .register_agent(&path, capability.as_str())
+            .await
+            .map_err(Error::from)?;
+        if let Some(inner) = &mut handle.inner {
+            inner.registered = true;
+        }
+
+        if options.default {
+            manager
+                .request_default_agent(&path)
+                .await
+                .map_err(Error::from)?;
+        }
+
+        Ok(handle)
+    }
+
+    /// Unregisters the [`Agent`].
+    ///
+    /// Dropping an [`AgentHandle`] has the same effect, but happens in the background and any
+    /// error is only logged. This method allows waiting for completion and observing errors.
+    pub async fn unregister(mut self) -> Result<()> {
+        match self.inner.take() {
+            Some(inner) => inner.unregister().await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Registration {
+    async fn unregister(self) -> Result<()> {
+        let res = if self.registered {
+            let builder = AgentManagerProxy::builder(&self.session.conn);
+            match self.session.proxy(builder, "/org/bluez").await {
+                Ok(manager) => manager
+                    .unregister_agent(&self.path)
+                    .await
+                    .map_err(Error::from),
+                Err(e) => Err(e),
+            }
+        } else {
+            Ok(())
+        };
+        (self.remove)(self.session.conn, self.path).await;
+        res
+    }
+}
+
+impl Drop for AgentHandle {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            executor::spawn_detached(async move {
+                let path = inner.path.clone();
+                if let Err(e) = inner.unregister().await {
+                    log::warn!("failed to unregister agent {}: {}", path, e);
+                }
+            });
+        }
+    }
+}
+
+fn remove_interface<A: Agent>(
+    conn: Connection,
+    path: ObjectPath<'static>,
+) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+    Box::pin(async move {
+        if let Err(e) = conn
+            .object_server()
+            .remove::<AgentInterface<A>, _>(&path)
+            .await
+        {
+            log::warn!("failed to remove agent object {}: {}", path, e);
+        }
+    })
+}
+
+/// An [`Agent`] that pairs using a fixed passkey.
+///
+/// This is useful for devices with a passkey that is printed on the device or otherwise known in
+/// advance. Passkey confirmation requests are only accepted if the passkey matches.
+#[derive(Debug, Clone)]
+pub struct FixedPasskey {
+    passkey: u32,
+}
+
+impl FixedPasskey {
+    /// Creates a [`FixedPasskey`] agent that responds with `passkey`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `passkey` is not in the valid range `0..=999999`.
+    pub fn new(passkey: u32) -> Self {
+        assert!(passkey <= 999999, "invalid passkey {}", passkey);
+        Self { passkey }
+    }
+}
+
+impl Agent for FixedPasskey {
+    fn capability(&self) -> Capability {
+        Capability::KeyboardOnly
+    }
+
+    async fn request_pin_code(&self, _: &Device) -> std::result::Result<String, AgentError> {
+        Ok(format!("{:06}", self.passkey))
+    }
+
+    async fn request_passkey(&self, _: &Device) -> std::result::Result<u32, AgentError> {
+        Ok(self.passkey)
+    }
+
+    async fn request_confirmation(
+        &self,
+        _: &Device,
+        passkey: u32,
+    ) -> std::result::Result<(), AgentError> {
+        if passkey == self.passkey {
+            Ok(())
+        } else {
+            Err(AgentError::Rejected)
+        }
+    }
+
+    async fn authorize_service(&self, _: &Device, _: Uuid) -> std::result::Result<(), AgentError> {
+        Ok(())
+    }
+}
+
+/// An [`Agent`] that accepts every request that does not require input.
+///
+/// This uses "Just Works" pairing, which does not protect against man-in-the-middle attacks.
+/// Requests for a PIN code or passkey are rejected.
+#[derive(Debug, Clone, Default)]
+pub struct AutoAccept;
+
+impl Agent for AutoAccept {
+    fn capability(&self) -> Capability {
+        Capability::NoInputNoOutput
+    }
+
+    async fn request_confirmation(
+        &self,
+        _: &Device,
+        _: u32,
+    ) -> std::result::Result<(), AgentError> {
+        Ok(())
+    }
+
+    async fn request_authorization(&self, _: &Device) -> std::result::Result<(), AgentError> {
+        Ok(())
+    }
+
+    async fn authorize_service(&self, _: &Device, _: Uuid) -> std::result::Result<(), AgentError> {
+        Ok(())
+    }
+}
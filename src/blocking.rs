@@ -0,0 +1,1234 @@
+//! A blocking (synchronous) API mirroring the async one.
+//!
+//! The types in this module wrap their async counterparts and drive them to completion on an
+//! internal executor thread, so they can be used from synchronous code without pulling in an async
+//! runtime. Streams are exposed as [`Iterator`]s whose [`Iterator::next`] blocks until the next
+//! item arrives, or until the timeout configured via `set_timeout` elapses (in which case it
+//! returns [`None`]). Since stream errors are permanent, the iterators end after yielding an error.
+//!
+//! With the `tokio` feature, the executor thread runs its own tokio runtime, so this module can be
+//! used without one as well.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use blues::blocking::{Adapter, Session};
+//! use std::time::Duration;
+//!
+//! # fn main() -> blues::Result<()> {
+//! let session = Session::new()?;
+//! let adapter = Adapter::open(&session)?;
+//! let _guard = adapter.discover()?;
+//! let mut devices = adapter.device_stream()?;
+//! devices.set_timeout(Some(Duration::from_secs(10)));
+//! for device in devices {
+//!     let device = device?;
+//!     println!("{:?}: {:?}", device.address()?, device.alias()?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::HashMap, fmt, future::Future, ops::Deref, os::unix::net::UnixStream, sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    address::{Address, AddressType},
+    agent::{self, Agent, AgentOptions},
+    device::{self, PropertyChange, PropertyName},
+    executor::{self, Task},
+    gatt::{
+        self, CharacteristicFlags, DescriptorFlags, PresentationFormat, ReadOptions, WriteOptions,
+    },
+    uuid::Uuid,
+    AdapterChange, DiscoveryFilter, PowerState, Result,
+};
+
+/// Runs `future` on the executor thread and blocks until it completes.
+fn block_on<F>(future: F) -> F::Output
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    executor::spawn(future).wait()
+}
+
+/// Calls the async method `f` on a shared async object and blocks until it completes.
+fn call<T, F, Fut>(inner: &Arc<T>, f: F) -> Fut::Output
+where
+    T: Send + Sync + 'static,
+    F: FnOnce(Arc<T>) -> Fut,
+    Fut: Future + Send + 'static,
+    Fut::Output: Send + 'static,
+{
+    block_on(f(inner.clone()))
+}
+
+/// Drops `value` on the executor thread.
+///
+/// With the `tokio` feature, dropping zbus objects can spawn tasks, which only works within the
+/// tokio runtime of the executor thread.
+fn drop_on_executor<T: Send + 'static>(value: T) {
+    executor::spawn_detached(async move { drop(value) });
+}
+
+/// Owns an async object, and drops it on the executor thread.
+struct OnExecutor<T: Send + 'static>(Option<T>);
+
+impl<T: Send + 'static> OnExecutor<T> {
+    fn new(value: T) -> Self {
+        Self(Some(value))
+    }
+
+    fn into_inner(mut self) -> T {
+        self.0.take().unwrap()
+    }
+}
+
+impl<T: Send + 'static> Deref for OnExecutor<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.0.as_ref().unwrap()
+    }
+}
+
+impl<T: Send + fmt::Debug + 'static> fmt::Debug for OnExecutor<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: Send + 'static> Drop for OnExecutor<T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.0.take() {
+            drop_on_executor(value);
+        }
+    }
+}
+
+/// Drives an async stream on the executor thread, one item at a time.
+///
+/// While an item is being waited for, the stream is owned by the executor thread. If the wait
+/// times out, the stream keeps waiting in the background, so that no item is lost.
+///
+/// Errors returned by the async streams are permanent, so this stops after yielding one.
+struct Pull<S: Send + 'static, T: Send + 'static> {
+    /// The stream, if no item is currently being waited for.
+    stream: Option<S>,
+    pending: Option<Task<(S, Result<T>)>>,
+    timeout: Option<Duration>,
+    failed: bool,
+}
+
+impl<S: Send + 'static, T: Send + 'static> Pull<S, T> {
+    fn new(stream: S) -> Self {
+        Self {
+            stream: Some(stream),
+            pending: None,
+            timeout: None,
+            failed: false,
+        }
+    }
+
+    fn next<F, Fut>(&mut self, next: F) -> Option<Result<T>>
+    where
+        F: FnOnce(S) -> Fut,
+        Fut: Future<Output = (S, Result<T>)> + Send + 'static,
+    {
+        if self.failed {
+            return None;
+        }
+
+        let pending = match &mut self.pending {
+            Some(pending) => pending,
+            None => {
+                let stream = self.stream.take().expect("stream went missing");
+                self.pending.insert(executor::spawn(next(stream)))
+            }
+        };
+
+        let (stream, item) = pending.wait_timeout(self.timeout)?;
+        self.pending = None;
+        self.stream = Some(stream);
+        self.failed = item.is_err();
+        Some(item)
+    }
+
+    /// Returns the stream, or [`None`] if it is still waiting for an item (in which case it is
+    /// dropped on the executor thread).
+    fn into_inner(mut self) -> Option<S> {
+        self.stream.take()
+    }
+}
+
+impl<S: Send + 'static, T: Send + 'static> Drop for Pull<S, T> {
+    fn drop(&mut self) {
+        // A pending stream is dropped on the executor thread when its `Task` is cancelled.
+        if let Some(stream) = self.stream.take() {
+            drop_on_executor(stream);
+        }
+    }
+}
+
+/// A cloneable handle to a D-Bus connection.
+///
+/// Blocking version of [`crate::Session`].
+pub struct Session {
+    inner: OnExecutor<crate::Session>,
+}
+
+impl Clone for Session {
+    fn clone(&self) -> Self {
+        Self::from(self.inner.clone())
+    }
+}
+
+impl Session {
+    /// Creates a new D-Bus connection.
+    ///
+    /// This connects to BlueZ on the system bus. Use [`Session::builder`] to connect to a different
+    /// bus or BlueZ instance.
+    pub fn new() -> Result<Self> {
+        Self::builder().build()
+    }
+
+    /// Returns a [`SessionBuilder`] for configuring which bus and BlueZ instance to connect to.
+    pub fn builder() -> SessionBuilder {
+        SessionBuilder {
+            inner: crate::Session::builder(),
+        }
+    }
+}
+
+impl From<crate::Session> for Session {
+    /// Wraps an existing (async) [`crate::Session`].
+    fn from(inner: crate::Session) -> Self {
+        Self {
+            inner: OnExecutor::new(inner),
+        }
+    }
+}
+
+/// Configures which bus and BlueZ instance a [`Session`] connects to.
+///
+/// Blocking version of [`crate::SessionBuilder`].
+pub struct SessionBuilder {
+    inner: crate::SessionBuilder,
+}
+
+impl SessionBuilder {
+    /// See [`crate::SessionBuilder::system_bus`].
+    pub fn system_bus(self) -> Self {
+        Self {
+            inner: self.inner.system_bus(),
+        }
+    }
+
+    /// See [`crate::SessionBuilder::session_bus`].
+    pub fn session_bus(self) -> Self {
+        Self {
+            inner: self.inner.session_bus(),
+        }
+    }
+
+    /// See [`crate::SessionBuilder::address`].
+    pub fn address(self, address: impl Into<String>) -> Self {
+        Self {
+            inner: self.inner.address(address),
+        }
+    }
+
+    /// See [`crate::SessionBuilder::unix_stream`].
+    pub fn unix_stream(self, stream: UnixStream) -> Self {
+        Self {
+            inner: self.inner.unix_stream(stream),
+        }
+    }
+
+    /// See [`crate::SessionBuilder::service_name`].
+    pub fn service_name(self, name: impl Into<String>) -> Self {
+        Self {
+            inner: self.inner.service_name(name),
+        }
+    }
+
+    /// Connects to BlueZ and returns the [`Session`].
+    pub fn build(self) -> Result<Session> {
+        block_on(self.inner.build()).map(Session::from)
+    }
+}
+
+/// A BlueZ Bluetooth adapter.
+///
+/// Blocking version of [`crate::Adapter`].
+#[derive(Debug)]
+pub struct Adapter {
+    inner: OnExecutor<Arc<crate::Adapter>>,
+}
+
+impl Adapter {
+    fn new(inner: crate::Adapter) -> Self {
+        Self {
+            inner: OnExecutor::new(Arc::new(inner)),
+        }
+    }
+
+    /// See [`crate::Adapter::open`].
+    pub fn open(session: &Session) -> Result<Self> {
+        let session = session.inner.clone();
+        block_on(async move { crate::Adapter::open(&session).await }).map(Self::new)
+    }
+
+    /// See [`crate::Adapter::open_by_name`].
+    pub fn open_by_name(session: &Session, name: &str) -> Result<Self> {
+        let session = session.inner.clone();
+        let name = name.to_string();
+        block_on(async move { crate::Adapter::open_by_name(&session, &name).await }).map(Self::new)
+    }
+
+    /// See [`crate::Adapter::open_by_address`].
+    pub fn open_by_address(session: &Session, address: Address) -> Result<Self> {
+        let session = session.inner.clone();
+        block_on(async move { crate::Adapter::open_by_address(&session, address).await })
+            .map(Self::new)
+    }
+
+    /// See [`crate::Adapter::enumerate`].
+    pub fn enumerate(session: &Session) -> Result<impl Iterator<Item = Self>> {
+        let session = session.inner.clone();
+        let adapters = block_on(async move {
+            crate::Adapter::enumerate(&session)
+                .await
+                .map(Iterator::collect::<Vec<_>>)
+        })?;
+        // Wrap all of them right away, so that they're dropped on the executor thread.
+        let adapters = adapters.into_iter().map(Self::new).collect::<Vec<_>>();
+        Ok(adapters.into_iter())
+    }
+
+    /// See [`crate::Adapter::hotplug_stream`].
+    pub fn hotplug_stream(session: &Session) -> Result<AdapterStream> {
+        let session = session.inner.clone();
+        let stream = block_on(async move { crate::Adapter::hotplug_stream(&session).await })?;
+        Ok(AdapterStream {
+            inner: Pull::new(stream),
+        })
+    }
+
+    /// Returns the adapter's device name (eg. `hci0`).
+    pub fn device_name(&self) -> &str {
+        self.inner.device_name()
+    }
+
+    /// See [`crate::Adapter::address`].
+    pub fn address(&self) -> Result<Address> {
+        call(&self.inner, |a| async move { a.address().await })
+    }
+
+    /// See [`crate::Adapter::address_type`].
+    pub fn address_type(&self) -> Result<AddressType> {
+        call(&self.inner, |a| async move { a.address_type().await })
+    }
+
+    /// See [`crate::Adapter::name`].
+    pub fn name(&self) -> Result<String> {
+        call(&self.inner, |a| async move { a.name().await })
+    }
+
+    /// See [`crate::Adapter::alias`].
+    pub fn alias(&self) -> Result<String> {
+        call(&self.inner, |a| async move { a.alias().await })
+    }
+
+    /// See [`crate::Adapter::set_alias`].
+    pub fn set_alias(&self, alias: &str) -> Result<()> {
+        let alias = alias.to_string();
+        call(&self.inner, |a| async move { a.set_alias(&alias).await })
+    }
+
+    /// See [`crate::Adapter::class`].
+    pub fn class(&self) -> Result<u32> {
+        call(&self.inner, |a| async move { a.class().await })
+    }
+
+    /// See [`crate::Adapter::is_powered`].
+    pub fn is_powered(&self) -> Result<bool> {
+        call(&self.inner, |a| async move { a.is_powered().await })
+    }
+
+    /// See [`crate::Adapter::set_powered`].
+    pub fn set_powered(&self, powered: bool) -> Result<()> {
+        call(&self.inner, |a| async move { a.set_powered(powered).await })
+    }
+
+    /// See [`crate::Adapter::power_state`].
+    pub fn power_state(&self) -> Result<PowerState> {
+        call(&self.inner, |a| async move { a.power_state().await })
+    }
+
+    /// See [`crate::Adapter::ensure_powered`].
+    pub fn ensure_powered(&self) -> Result<()> {
+        call(&self.inner, |a| async move { a.ensure_powered().await })
+    }
+
+    /// See [`crate::Adapter::is_discoverable`].
+    pub fn is_discoverable(&self) -> Result<bool> {
+        call(&self.inner, |a| async move { a.is_discoverable().await })
+    }
+
+    /// See [`crate::Adapter::set_discoverable`].
+    pub fn set_discoverable(&self, discoverable: bool) -> Result<()> {
+        call(&self.inner, |a| async move {
+            a.set_discoverable(discoverable).await
+        })
+    }
+
+    /// See [`crate::Adapter::discoverable_timeout`].
+    pub fn discoverable_timeout(&self) -> Result<Option<Duration>> {
+        call(
+            &self.inner,
+            |a| async move { a.discoverable_timeout().await },
+        )
+    }
+
+    /// See [`crate::Adapter::set_discoverable_timeout`].
+    pub fn set_discoverable_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        call(&self.inner, |a| async move {
+            a.set_discoverable_timeout(timeout).await
+        })
+    }
+
+    /// See [`crate::Adapter::is_pairable`].
+    pub fn is_pairable(&self) -> Result<bool> {
+        call(&self.inner, |a| async move { a.is_pairable().await })
+    }
+
+    /// See [`crate::Adapter::set_pairable`].
+    pub fn set_pairable(&self, pairable: bool) -> Result<()> {
+        call(
+            &self.inner,
+            |a| async move { a.set_pairable(pairable).await },
+        )
+    }
+
+    /// See [`crate::Adapter::pairable_timeout`].
+    pub fn pairable_timeout(&self) -> Result<Option<Duration>> {
+        call(&self.inner, |a| async move { a.pairable_timeout().await })
+    }
+
+    /// See [`crate::Adapter::set_pairable_timeout`].
+    pub fn set_pairable_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        call(&self.inner, |a| async move {
+            a.set_pairable_timeout(timeout).await
+        })
+    }
+
+    /// See [`crate::Adapter::is_connectable`].
+    pub fn is_connectable(&self) -> Result<bool> {
+        call(&self.inner, |a| async move { a.is_connectable().await })
+    }
+
+    /// See [`crate::Adapter::set_connectable`].
+    pub fn set_connectable(&self, connectable: bool) -> Result<()> {
+        call(&self.inner, |a| async move {
+            a.set_connectable(connectable).await
+        })
+    }
+
+    /// See [`crate::Adapter::discover`].
+    pub fn discover(&self) -> Result<DiscoveryGuard> {
+        let inner = call(&self.inner, |a| async move { a.discover().await })?;
+        Ok(DiscoveryGuard {
+            inner: OnExecutor::new(inner),
+        })
+    }
+
+    /// See [`crate::Adapter::discover_with`].
+    pub fn discover_with(&self, filter: &DiscoveryFilter) -> Result<DiscoveryGuard> {
+        let filter = filter.clone();
+        let inner = call(
+            &self.inner,
+            |a| async move { a.discover_with(&filter).await },
+        )?;
+        Ok(DiscoveryGuard {
+            inner: OnExecutor::new(inner),
+        })
+    }
+
+    /// See [`crate::Adapter::start_discovery`].
+    pub fn start_discovery(&self) -> Result<()> {
+        call(&self.inner, |a| async move { a.start_discovery().await })
+    }
+
+    /// See [`crate::Adapter::stop_discovery`].
+    pub fn stop_discovery(&self) -> Result<()> {
+        call(&self.inner, |a| async move { a.stop_discovery().await })
+    }
+
+    /// See [`crate::Adapter::set_discovery_filter`].
+    pub fn set_discovery_filter(&self, filter: &DiscoveryFilter) -> Result<()> {
+        let filter = filter.clone();
+        call(&self.inner, |a| async move {
+            a.set_discovery_filter(&filter).await
+        })
+    }
+
+    /// See [`crate::Adapter::discovery_filters`].
+    pub fn discovery_filters(&self) -> Result<Vec<String>> {
+        call(&self.inner, |a| async move { a.discovery_filters().await })
+    }
+
+    /// See [`crate::Adapter::is_discovering`].
+    pub fn is_discovering(&self) -> Result<bool> {
+        call(&self.inner, |a| async move { a.is_discovering().await })
+    }
+
+    /// See [`crate::Adapter::device`].
+    pub fn device(&self, address: Address) -> Result<Option<Device>> {
+        let device = call(&self.inner, |a| async move { a.device(address).await })?;
+        Ok(device.map(Device::new))
+    }
+
+    /// See [`crate::Adapter::connect_device`].
+    pub fn connect_device(&self, address: Address, address_type: AddressType) -> Result<Device> {
+        call(&self.inner, |a| async move {
+            a.connect_device(address, address_type).await
+        })
+        .map(Device::new)
+    }
+
+    /// See [`crate::Adapter::remove_device`].
+    pub fn remove_device(&self, device: &Device) -> Result<()> {
+        let device = device.inner.clone();
+        call(
+            &self.inner,
+            |a| async move { a.remove_device(&device).await },
+        )
+    }
+
+    /// See [`crate::Adapter::change_stream`].
+    pub fn change_stream(&self) -> Result<AdapterChanges> {
+        let stream = call(&self.inner, |a| async move { a.change_stream().await })?;
+        Ok(AdapterChanges {
+            inner: Pull::new(stream),
+        })
+    }
+
+    /// See [`crate::Adapter::device_stream`].
+    pub fn device_stream(&self) -> Result<DeviceStream> {
+        let stream = call(&self.inner, |a| async move { a.device_stream().await })?;
+        Ok(DeviceStream {
+            inner: Pull::new(stream),
+        })
+    }
+
+    /// See [`crate::Adapter::device_set`].
+    pub fn device_set<I: IntoIterator<Item = PropertyName>>(
+        &self,
+        properties: I,
+    ) -> Result<DeviceSet> {
+        let interest = properties.into_iter().collect::<Vec<_>>();
+        let set = call(&self.inner, |a| async move { a.device_set(interest).await })?;
+        let devices = set.devices().iter().cloned().map(Device::new).collect();
+        Ok(DeviceSet {
+            inner: Pull::new(set),
+            devices,
+        })
+    }
+}
+
+/// An iterator over Bluetooth adapters being added to or removed from the system.
+///
+/// Blocking version of [`crate::AdapterStream`], returned by [`Adapter::hotplug_stream`].
+pub struct AdapterStream {
+    inner: Pull<crate::AdapterStream, crate::AdapterEvent>,
+}
+
+impl AdapterStream {
+    /// Sets how long [`Iterator::next`] waits for the next event before returning [`None`].
+    ///
+    /// [`None`] (the default) means to wait indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.timeout = timeout;
+    }
+}
+
+impl Iterator for AdapterStream {
+    type Item = Result<AdapterEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let event = self.inner.next(|mut stream| async move {
+            let event = stream.next().await;
+            (stream, event)
+        })?;
+        Some(event.map(|event| match event {
+            crate::AdapterEvent::Added(adapter) => AdapterEvent::Added(Adapter::new(adapter)),
+            crate::AdapterEvent::Removed(name) => AdapterEvent::Removed(name),
+        }))
+    }
+}
+
+/// An event reported by an [`AdapterStream`].
+#[derive(Debug)]
+pub enum AdapterEvent {
+    /// The given [`Adapter`] was added to the system.
+    Added(Adapter),
+    /// The adapter with the given device name (eg. `hci0`) was removed from the system.
+    Removed(String),
+}
+
+/// An iterator over changes to the state of an [`Adapter`].
+///
+/// Blocking version of [`crate::AdapterChanges`], returned by [`Adapter::change_stream`].
+pub struct AdapterChanges {
+    inner: Pull<crate::AdapterChanges, AdapterChange>,
+}
+
+impl AdapterChanges {
+    /// Sets how long [`Iterator::next`] waits for the next change before returning [`None`].
+    ///
+    /// [`None`] (the default) means to wait indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.timeout = timeout;
+    }
+}
+
+impl Iterator for AdapterChanges {
+    type Item = Result<AdapterChange>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next(|mut stream| async move {
+            let change = stream.next().await;
+            (stream, change)
+        })
+    }
+}
+
+/// Keeps device discovery on an [`Adapter`] running while alive.
+///
+/// Blocking version of [`crate::DiscoveryGuard`], returned by [`Adapter::discover`] and
+/// [`Adapter::discover_with`].
+pub struct DiscoveryGuard {
+    inner: OnExecutor<crate::DiscoveryGuard>,
+}
+
+impl DiscoveryGuard {
+    /// See [`crate::DiscoveryGuard::stop`].
+    pub fn stop(self) -> Result<()> {
+        block_on(self.inner.into_inner().stop())
+    }
+}
+
+/// An iterator yielding newly discovered or changed [`Device`]s.
+///
+/// Blocking version of [`crate::DeviceStream`], returned by [`Adapter::device_stream`].
+pub struct DeviceStream {
+    inner: Pull<crate::DeviceStream, device::Device>,
+}
+
+impl DeviceStream {
+    /// Sets how long [`Iterator::next`] waits for the next [`Device`] before returning [`None`].
+    ///
+    /// [`None`] (the default) means to wait indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.timeout = timeout;
+    }
+}
+
+impl Iterator for DeviceStream {
+    type Item = Result<Device>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let device = self.inner.next(|mut stream| async move {
+            let device = stream.next().await;
+            (stream, device)
+        })?;
+        Some(device.map(Device::new))
+    }
+}
+
+/// A set of [`Device`]s currently visible to an [`Adapter`], and an iterator over changes to it.
+///
+/// Blocking version of [`crate::DeviceSet`], returned by [`Adapter::device_set`]. Each item
+/// corresponds to a call to [`DeviceSet::change`][crate::DeviceSet::change].
+pub struct DeviceSet {
+    inner: Pull<crate::DeviceSet, DeviceSetChange>,
+    devices: Vec<Device>,
+}
+
+impl DeviceSet {
+    /// Returns the [`Device`]s in this [`DeviceSet`], as of the last change yielded by the
+    /// iterator.
+    pub fn devices(&self) -> &[Device] {
+        &self.devices
+    }
+
+    /// Sets how long [`Iterator::next`] waits for the next change before returning [`None`].
+    ///
+    /// [`None`] (the default) means to wait indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.timeout = timeout;
+    }
+}
+
+impl Iterator for DeviceSet {
+    type Item = Result<DeviceSetChange>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let change = self.inner.next(|mut set| async move {
+            let change = set.change().await.map(|change| match change {
+                crate::DeviceSetChange::Added(device) => {
+                    DeviceSetChange::Added(Device::new(device.clone()))
+                }
+                crate::DeviceSetChange::Removed(device) => {
+                    DeviceSetChange::Removed(Device::new(device))
+                }
+                crate::DeviceSetChange::Changed(device, change) => {
+                    DeviceSetChange::Changed(Device::new(device.clone()), change)
+                }
+            });
+            (set, change)
+        })?;
+        match &change {
+            Ok(DeviceSetChange::Added(device)) => self.devices.push(device.clone()),
+            Ok(DeviceSetChange::Removed(device)) => self
+                .devices
+                .retain(|dev| dev.inner.path() != device.inner.path()),
+            _ => {}
+        }
+        Some(change)
+    }
+}
+
+/// Describes a change to a [`DeviceSet`].
+///
+/// Blocking version of [`crate::DeviceSetChange`].
+#[derive(Debug)]
+pub enum DeviceSetChange {
+    /// The given [`Device`] was just added (discovered).
+    Added(Device),
+    /// The given [`Device`] was removed (calling any methods on it will probably fail).
+    Removed(Device),
+    /// A property of the [`Device`] was changed.
+    Changed(Device, PropertyChange),
+}
+
+/// A reference to a remote BlueZ device.
+///
+/// Blocking version of [`crate::device::Device`].
+#[derive(Debug)]
+pub struct Device {
+    inner: OnExecutor<Arc<device::Device>>,
+}
+
+impl Clone for Device {
+    fn clone(&self) -> Self {
+        Self {
+            inner: OnExecutor::new(self.inner.clone()),
+        }
+    }
+}
+
+impl Device {
+    fn new(inner: device::Device) -> Self {
+        Self {
+            inner: OnExecutor::new(Arc::new(inner)),
+        }
+    }
+
+    /// Returns the async [`Device`][device::Device] wrapped by this [`Device`].
+    ///
+    /// This is needed to pass the [`Device`] to [`ReadOptions::device`] and
+    /// [`WriteOptions::device`].
+    pub fn as_async(&self) -> &device::Device {
+        &self.inner
+    }
+
+    /// See [`crate::device::Device::address`].
+    pub fn address(&self) -> Result<Address> {
+        call(&self.inner, |d| async move { d.address().await })
+    }
+
+    /// See [`crate::device::Device::address_type`].
+    pub fn address_type(&self) -> Result<AddressType> {
+        call(&self.inner, |d| async move { d.address_type().await })
+    }
+
+    /// See [`crate::device::Device::alias`].
+    pub fn alias(&self) -> Result<String> {
+        call(&self.inner, |d| async move { d.alias().await })
+    }
+
+    /// See [`crate::device::Device::rssi`].
+    pub fn rssi(&self) -> Result<i16> {
+        call(&self.inner, |d| async move { d.rssi().await })
+    }
+
+    /// See [`crate::device::Device::service_uuids`].
+    pub fn service_uuids(&self) -> Result<Vec<Uuid>> {
+        call(&self.inner, |d| async move { d.service_uuids().await })
+    }
+
+    /// See [`crate::device::Device::manufacturer_data`].
+    pub fn manufacturer_data(&self) -> Result<HashMap<u16, Vec<u8>>> {
+        call(&self.inner, |d| async move { d.manufacturer_data().await })
+    }
+
+    /// See [`crate::device::Device::service_data`].
+    pub fn service_data(&self) -> Result<HashMap<Uuid, Vec<u8>>> {
+        call(&self.inner, |d| async move { d.service_data().await })
+    }
+
+    /// See [`crate::device::Device::tx_power`].
+    pub fn tx_power(&self) -> Result<i16> {
+        call(&self.inner, |d| async move { d.tx_power().await })
+    }
+
+    /// See [`crate::device::Device::advertising_flags`].
+    pub fn advertising_flags(&self) -> Result<Vec<u8>> {
+        call(&self.inner, |d| async move { d.advertising_flags().await })
+    }
+
+    /// See [`crate::device::Device::advertising_data`].
+    pub fn advertising_data(&self) -> Result<HashMap<u8, Vec<u8>>> {
+        call(&self.inner, |d| async move { d.advertising_data().await })
+    }
+
+    /// See [`crate::device::Device::gatt_services`].
+    pub fn gatt_services(&self) -> Result<Vec<Service>> {
+        let services = call(&self.inner, |d| async move { d.gatt_services().await })?;
+        Ok(services.into_iter().map(Service::new).collect())
+    }
+
+    /// See [`crate::device::Device::connect`].
+    pub fn connect(&self) -> Result<()> {
+        call(&self.inner, |d| async move { d.connect().await })
+    }
+
+    /// See [`crate::device::Device::disconnect`].
+    pub fn disconnect(&self) -> Result<()> {
+        call(&self.inner, |d| async move { d.disconnect().await })
+    }
+
+    /// See [`crate::device::Device::is_connected`].
+    pub fn is_connected(&self) -> Result<bool> {
+        call(&self.inner, |d| async move { d.is_connected().await })
+    }
+
+    /// See [`crate::device::Device::pair`].
+    pub fn pair(&self) -> Result<()> {
+        call(&self.inner, |d| async move { d.pair().await })
+    }
+
+    /// See [`crate::device::Device::cancel_pairing`].
+    pub fn cancel_pairing(&self) -> Result<()> {
+        call(&self.inner, |d| async move { d.cancel_pairing().await })
+    }
+
+    /// See [`crate::device::Device::is_paired`].
+    pub fn is_paired(&self) -> Result<bool> {
+        call(&self.inner, |d| async move { d.is_paired().await })
+    }
+
+    /// See [`crate::device::Device::is_bonded`].
+    pub fn is_bonded(&self) -> Result<bool> {
+        call(&self.inner, |d| async move { d.is_bonded().await })
+    }
+
+    /// See [`crate::device::Device::is_trusted`].
+    pub fn is_trusted(&self) -> Result<bool> {
+        call(&self.inner, |d| async move { d.is_trusted().await })
+    }
+
+    /// See [`crate::device::Device::set_trusted`].
+    pub fn set_trusted(&self, trusted: bool) -> Result<()> {
+        call(&self.inner, |d| async move { d.set_trusted(trusted).await })
+    }
+
+    /// See [`crate::device::Device::is_blocked`].
+    pub fn is_blocked(&self) -> Result<bool> {
+        call(&self.inner, |d| async move { d.is_blocked().await })
+    }
+
+    /// See [`crate::device::Device::set_blocked`].
+    pub fn set_blocked(&self, blocked: bool) -> Result<()> {
+        call(&self.inner, |d| async move { d.set_blocked(blocked).await })
+    }
+
+    /// See [`crate::device::Device::is_legacy_pairing`].
+    pub fn is_legacy_pairing(&self) -> Result<bool> {
+        call(&self.inner, |d| async move { d.is_legacy_pairing().await })
+    }
+
+    /// See [`crate::device::Device::is_wake_allowed`].
+    pub fn is_wake_allowed(&self) -> Result<bool> {
+        call(&self.inner, |d| async move { d.is_wake_allowed().await })
+    }
+
+    /// See [`crate::device::Device::set_wake_allowed`].
+    pub fn set_wake_allowed(&self, wake_allowed: bool) -> Result<()> {
+        call(&self.inner, |d| async move {
+            d.set_wake_allowed(wake_allowed).await
+        })
+    }
+
+    /// See [`crate::device::Device::property_change_stream`].
+    pub fn property_change_stream<I: IntoIterator<Item = PropertyName>>(
+        &self,
+        properties: I,
+    ) -> Result<Changes> {
+        let interest = properties.into_iter().collect::<Vec<_>>();
+        let stream = call(&self.inner, |d| async move {
+            d.property_change_stream(interest).await
+        })?;
+        Ok(Changes {
+            inner: Pull::new(stream),
+        })
+    }
+}
+
+/// An iterator over [`Device`] property changes, carrying the new values.
+///
+/// Blocking version of [`crate::device::Changes`], returned by
+/// [`Device::property_change_stream`]. Each item corresponds to a call to
+/// [`Changes::next_change`][device::Changes::next_change].
+pub struct Changes {
+    inner: Pull<device::Changes, PropertyChange>,
+}
+
+impl Changes {
+    /// Sets how long [`Iterator::next`] waits for the next change before returning [`None`].
+    ///
+    /// [`None`] (the default) means to wait indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.timeout = timeout;
+    }
+}
+
+impl Iterator for Changes {
+    type Item = Result<PropertyChange>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next(|mut stream| async move {
+            let change = stream.next_change().await;
+            (stream, change)
+        })
+    }
+}
+
+/// A GATT service of a Bluetooth LE device.
+///
+/// Blocking version of [`crate::gatt::Service`].
+pub struct Service {
+    inner: OnExecutor<Arc<gatt::Service>>,
+}
+
+impl Service {
+    fn new(inner: gatt::Service) -> Self {
+        Self {
+            inner: OnExecutor::new(Arc::new(inner)),
+        }
+    }
+
+    /// See [`crate::gatt::Service::uuid`].
+    pub fn uuid(&self) -> Result<Uuid> {
+        call(&self.inner, |s| async move { s.uuid().await })
+    }
+
+    /// See [`crate::gatt::Service::is_primary`].
+    pub fn is_primary(&self) -> Result<bool> {
+        call(&self.inner, |s| async move { s.is_primary().await })
+    }
+
+    /// See [`crate::gatt::Service::characteristic`].
+    pub fn characteristic(&self, uuid: Uuid) -> Result<Characteristic> {
+        call(&self.inner, |s| async move { s.characteristic(uuid).await }).map(Characteristic::new)
+    }
+
+    /// See [`crate::gatt::Service::characteristics`].
+    pub fn characteristics(&self) -> Result<Vec<Characteristic>> {
+        let characteristics = call(&self.inner, |s| async move { s.characteristics().await })?;
+        Ok(characteristics
+            .into_iter()
+            .map(Characteristic::new)
+            .collect())
+    }
+}
+
+/// A GATT characteristic of a Bluetooth LE device.
+///
+/// Blocking version of [`crate::gatt::Characteristic`].
+pub struct Characteristic {
+    inner: OnExecutor<Arc<gatt::Characteristic>>,
+}
+
+impl Characteristic {
+    fn new(inner: gatt::Characteristic) -> Self {
+        Self {
+            inner: OnExecutor::new(Arc::new(inner)),
+        }
+    }
+
+    /// See [`crate::gatt::Characteristic::uuid`].
+    pub fn uuid(&self) -> Result<Uuid> {
+        call(&self.inner, |c| async move { c.uuid().await })
+    }
+
+    /// See [`crate::gatt::Characteristic::mtu`].
+    pub fn mtu(&self) -> Result<u16> {
+        call(&self.inner, |c| async move { c.mtu().await })
+    }
+
+    /// See [`crate::gatt::Characteristic::flags`].
+    pub fn flags(&self) -> Result<CharacteristicFlags> {
+        call(&self.inner, |c| async move { c.flags().await })
+    }
+
+    /// See [`crate::gatt::Characteristic::descriptor`].
+    pub fn descriptor(&self, uuid: Uuid) -> Result<Descriptor> {
+        call(&self.inner, |c| async move { c.descriptor(uuid).await }).map(Descriptor::new)
+    }
+
+    /// See [`crate::gatt::Characteristic::descriptors`].
+    pub fn descriptors(&self) -> Result<Vec<Descriptor>> {
+        let descriptors = call(&self.inner, |c| async move { c.descriptors().await })?;
+        Ok(descriptors.into_iter().map(Descriptor::new).collect())
+    }
+
+    /// See [`crate::gatt::Characteristic::presentation_format`].
+    pub fn presentation_format(&self) -> Result<PresentationFormat> {
+        call(
+            &self.inner,
+            |c| async move { c.presentation_format().await },
+        )
+    }
+
+    /// See [`crate::gatt::Characteristic::subscribe`].
+    pub fn subscribe(&self) -> Result<ValueStream> {
+        let stream = call(&self.inner, |c| async move { c.subscribe().await })?;
+        Ok(ValueStream {
+            inner: Pull::new(stream),
+        })
+    }
+
+    /// See [`crate::gatt::Characteristic::acquire_notify`].
+    pub fn acquire_notify(&self) -> Result<ValueStream> {
+        let stream = call(&self.inner, |c| async move { c.acquire_notify().await })?;
+        Ok(ValueStream {
+            inner: Pull::new(stream),
+        })
+    }
+
+    /// See [`crate::gatt::Characteristic::acquire_write`].
+    pub fn acquire_write(&self) -> Result<ValueWriter> {
+        let writer = call(&self.inner, |c| async move { c.acquire_write().await })?;
+        Ok(ValueWriter {
+            inner: Some(writer),
+        })
+    }
+
+    /// See [`crate::gatt::Characteristic::read`].
+    pub fn read(&self) -> Result<Vec<u8>> {
+        call(&self.inner, |c| async move { c.read().await })
+    }
+
+    /// See [`crate::gatt::Characteristic::read_with`].
+    pub fn read_with(&self, options: &ReadOptions) -> Result<Vec<u8>> {
+        let options = options.clone();
+        call(&self.inner, |c| async move { c.read_with(&options).await })
+    }
+
+    /// See [`crate::gatt::Characteristic::write`].
+    pub fn write(&self, value: &[u8]) -> Result<()> {
+        let value = value.to_vec();
+        call(&self.inner, |c| async move { c.write(&value).await })
+    }
+
+    /// See [`crate::gatt::Characteristic::write_with`].
+    pub fn write_with(&self, value: &[u8], options: &WriteOptions) -> Result<()> {
+        let value = value.to_vec();
+        let options = options.clone();
+        call(&self.inner, |c| async move {
+            c.write_with(&value, &options).await
+        })
+    }
+}
+
+/// A GATT descriptor of a [`Characteristic`].
+///
+/// Blocking version of [`crate::gatt::Descriptor`].
+pub struct Descriptor {
+    inner: OnExecutor<Arc<gatt::Descriptor>>,
+}
+
+impl Descriptor {
+    fn new(inner: gatt::Descriptor) -> Self {
+        Self {
+            inner: OnExecutor::new(Arc::new(inner)),
+        }
+    }
+
+    /// See [`crate::gatt::Descriptor::uuid`].
+    pub fn uuid(&self) -> Result<Uuid> {
+        call(&self.inner, |d| async move { d.uuid().await })
+    }
+
+    /// See [`crate::gatt::Descriptor::flags`].
+    pub fn flags(&self) -> Result<DescriptorFlags> {
+        call(&self.inner, |d| async move { d.flags().await })
+    }
+
+    /// See [`crate::gatt::Descriptor::read`].
+    pub fn read(&self) -> Result<Vec<u8>> {
+        call(&self.inner, |d| async move { d.read().await })
+    }
+
+    /// See [`crate::gatt::Descriptor::read_with`].
+    pub fn read_with(&self, options: &ReadOptions) -> Result<Vec<u8>> {
+        let options = options.clone();
+        call(&self.inner, |d| async move { d.read_with(&options).await })
+    }
+
+    /// See [`crate::gatt::Descriptor::write`].
+    pub fn write(&self, value: &[u8]) -> Result<()> {
+        let value = value.to_vec();
+        call(&self.inner, |d| async move { d.write(&value).await })
+    }
+
+    /// See [`crate::gatt::Descriptor::write_with`].
+    pub fn write_with(&self, value: &[u8], options: &WriteOptions) -> Result<()> {
+        let value = value.to_vec();
+        let options = options.clone();
+        call(&self.inner, |d| async move {
+            d.write_with(&value, &options).await
+        })
+    }
+}
+
+/// An iterator over the values of a [`Characteristic`] that has notifications or indications
+/// enabled.
+///
+/// Blocking version of [`crate::gatt::ValueStream`], returned by [`Characteristic::subscribe`] and
+/// [`Characteristic::acquire_notify`].
+pub struct ValueStream {
+    inner: Pull<gatt::ValueStream, Vec<u8>>,
+}
+
+impl ValueStream {
+    /// Sets how long [`Iterator::next`] waits for the next value before returning [`None`].
+    ///
+    /// [`None`] (the default) means to wait indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.timeout = timeout;
+    }
+
+    /// See [`crate::gatt::ValueStream::unsubscribe`].
+    ///
+    /// If a previous call to [`Iterator::next`] timed out, the [`ValueStream`] is still waiting
+    /// for a value in the background. In that case, it is dropped instead, which unsubscribes in
+    /// the background.
+    pub fn unsubscribe(self) -> Result<()> {
+        match self.inner.into_inner() {
+            Some(stream) => block_on(stream.unsubscribe()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Iterator for ValueStream {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next(|mut stream| async move {
+            let value = stream.next().await;
+            (stream, value)
+        })
+    }
+}
+
+/// A handle for writing values to a [`Characteristic`] without response.
+///
+/// Blocking version of [`crate::gatt::ValueWriter`], returned by
+/// [`Characteristic::acquire_write`].
+pub struct ValueWriter {
+    /// Moved to the executor thread while a write is in progress.
+    inner: Option<gatt::ValueWriter>,
+}
+
+impl ValueWriter {
+    fn inner(&self) -> &gatt::ValueWriter {
+        self.inner.as_ref().expect("value writer went missing")
+    }
+
+    /// See [`crate::gatt::ValueWriter::mtu`].
+    pub fn mtu(&self) -> u16 {
+        self.inner().mtu()
+    }
+
+    /// See [`crate::gatt::ValueWriter::is_acquired`].
+    pub fn is_acquired(&self) -> bool {
+        self.inner().is_acquired()
+    }
+
+    /// See [`crate::gatt::ValueWriter::write`].
+    pub fn write(&mut self, value: &[u8]) -> Result<()> {
+        let mut writer = self.inner.take().expect("value writer went missing");
+        let value = value.to_vec();
+        let (writer, res) = block_on(async move {
+            let res = writer.write(&value).await;
+            (writer, res)
+        });
+        self.inner = Some(writer);
+        res
+    }
+}
+
+impl Drop for ValueWriter {
+    fn drop(&mut self) {
+        if let Some(writer) = self.inner.take() {
+            drop_on_executor(writer);
+        }
+    }
+}
+
+/// A registered [`Agent`].
+///
+/// Blocking version of [`crate::agent::AgentHandle`].
+///
+/// The [`Agent`]'s methods are called on the internal executor thread that also drives all other
+/// blocking calls, so they should not block for long.
+pub struct AgentHandle {
+    inner: OnExecutor<agent::AgentHandle>,
+}
+
+impl AgentHandle {
+    /// See [`crate::agent::AgentHandle::register`].
+    pub fn register<A: Agent>(session: &Session, agent: A) -> Result<Self> {
+        Self::register_with(session, agent, &AgentOptions::new())
+    }
+
+    /// See [`crate::agent::AgentHandle::register_with`].
+    pub fn register_with<A: Agent>(
+        session: &Session,
+        agent: A,
+        options: &AgentOptions,
+    ) -> Result<Self> {
+        let session = session.inner.clone();
+        let options = options.clone();
+        let inner =
+            block_on(
+                async move { agent::AgentHandle::register_with(&session, agent, &options).await },
+            )?;
+        Ok(Self {
+            inner: OnExecutor::new(inner),
+        })
+    }
+
+    /// See [`crate::agent::AgentHandle::unregister`].
+    pub fn unregister(self) -> Result<()> {
+        block_on(self.inner.into_inner().unregister())
+    }
+}
@@ -0,0 +1,385 @@
+//! A mirror of the BlueZ object tree, kept up to date via D-Bus signals.
+//!
+//! Fetching the whole tree via `GetManagedObjects` can transfer a lot of data when many devices
+//! have been discovered, so lookups are served from this mirror instead. The tree is fetched once,
+//! on first use, and then updated via `InterfacesAdded`, `InterfacesRemoved` and
+//! `PropertiesChanged`.
+
+use std::{
+    collections::HashMap,
+    future::poll_fn,
+    mem,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+    task::{Context, Poll, Waker},
+};
+
+use futures_util::Stream;
+use zbus::{
+    fdo::{DBusProxy, ObjectManagerProxy},
+    names::{BusName, OwnedUniqueName},
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue},
+    Connection, MatchRule, Message, MessageStream, MessageType,
+};
+
+use crate::{executor, Error, Result};
+
+type Properties = HashMap<String, OwnedValue>;
+type Interfaces = HashMap<String, Properties>;
+type Objects = HashMap<OwnedObjectPath, Interfaces>;
+
+pub(crate) struct ObjectCache {
+    manager: ObjectManagerProxy<'static>,
+    state: Mutex<State>,
+}
+
+struct State {
+    service: BusName<'static>,
+    /// Whether the connection is to a message bus (as opposed to a peer-to-peer connection).
+    is_bus: bool,
+    /// The unique name of the BlueZ instance, if known.
+    owner: Option<OwnedUniqueName>,
+    /// Incremented whenever `objects` is invalidated by a change of `owner`.
+    generation: u64,
+    /// The mirrored object tree, or `None` if it hasn't been fetched yet.
+    objects: Option<Objects>,
+    /// Number of `GetManagedObjects` calls currently in flight.
+    fetching: usize,
+    /// Signals received while a snapshot is being fetched, to be applied on top of it.
+    pending: Vec<Arc<Message>>,
+    signals: MessageStream,
+    owner_changes: Option<MessageStream>,
+    /// The waker of the background task that keeps draining the signal streams.
+    waker: Option<Waker>,
+}
+
+impl ObjectCache {
+    pub(crate) async fn new(conn: &Connection, service: BusName<'static>) -> Result<Arc<Self>> {
+        let manager = ObjectManagerProxy::builder(conn)
+            .destination(service.clone())
+            .map_err(Error::from)?
+            .path("/")
+            .map_err(Error::from)?
+            .build()
+            .await
+            .map_err(Error::from)?;
+
+        // Subscribe to the signals before doing anything else, so that no change can be missed.
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(service.clone())
+            .map_err(Error::from)?
+            .build();
+        let signals = MessageStream::for_match_rule(rule, conn, None)
+            .await
+            .map_err(Error::from)?;
+
+        let (owner_changes, owner) = if conn.is_bus() {
+            let rule = MatchRule::builder()
+                .msg_type(MessageType::Signal)
+                .sender("org.freedesktop.DBus")
+                .map_err(Error::from)?
+                .interface("org.freedesktop.DBus")
+                .map_err(Error::from)?
+                .member("NameOwnerChanged")
+                .map_err(Error::from)?
+                .arg(0, service.as_str())
+                .map_err(Error::from)?
+                .build();
+            let owner_changes = MessageStream::for_match_rule(rule, conn, None)
+                .await
+                .map_err(Error::from)?;
+            let dbus = DBusProxy::new(conn).await.map_err(Error::from)?;
+            // Fails if BlueZ isn't running (yet).
+            let owner = dbus.get_name_owner(service.clone()).await.ok();
+            (Some(owner_changes), owner)
+        } else {
+            (None, None)
+        };
+
+        let this = Arc::new(Self {
+            manager,
+            state: Mutex::new(State {
+                service,
+                is_bus: conn.is_bus(),
+                owner,
+                generation: 0,
+                objects: None,
+                fetching: 0,
+                pending: Vec::new(),
+                signals,
+                owner_changes,
+                waker: None,
+            }),
+        });
+
+        let weak = Arc::downgrade(&this);
+        executor::spawn_detached(drive(weak));
+
+        Ok(this)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Invokes `f` with the up-to-date object tree, fetching it first if necessary.
+    async fn with<R>(&self, f: impl FnOnce(&Objects) -> R) -> Result<R> {
+        loop {
+            let generation = {
+                let mut state = self.lock();
+                state.drain();
+                if let Some(objects) = &state.objects {
+                    return Ok(f(objects));
+                }
+                state.fetching += 1;
+                state.generation
+            };
+
+            log::debug!("fetching BlueZ object tree");
+            let res = self.manager.get_managed_objects().await;
+
+            let mut state = self.lock();
+            state.fetching -= 1;
+            match res {
+                Ok(snapshot) => {
+                    if state.objects.is_none() && state.generation == generation {
+                        let objects = snapshot
+                            .into_iter()
+                            .map(|(path, intfs)| {
+                                let intfs = intfs
+                                    .into_iter()
+                                    .map(|(name, props)| (name.to_string(), props))
+                                    .collect();
+                                (path, intfs)
+                            })
+                            .collect();
+                        state.objects = Some(objects);
+                        for message in mem::take(&mut state.pending) {
+                            state.apply(&message);
+                        }
+                    }
+                }
+                Err(e) => {
+                    if state.fetching == 0 {
+                        state.pending.clear();
+                    }
+                    return Err(Error::from(e));
+                }
+            }
+        }
+    }
+
+    /// Returns the path and properties of every object below `prefix` that implements `interface`.
+    pub(crate) async fn find(
+        &self,
+        prefix: &str,
+        interface: &str,
+    ) -> Result<Vec<(OwnedObjectPath, Properties)>> {
+        self.with(|objects| {
+            objects
+                .iter()
+                .filter(|(path, _)| is_below(path, prefix))
+                .filter_map(|(path, intfs)| Some((path.clone(), intfs.get(interface)?.clone())))
+                .collect()
+        })
+        .await
+    }
+
+    /// Returns the value of a property, or `None` if the object or property isn't known.
+    pub(crate) async fn property(
+        &self,
+        path: &ObjectPath<'_>,
+        interface: &str,
+        name: &str,
+    ) -> Result<Option<OwnedValue>> {
+        let path = OwnedObjectPath::from(path.to_owned());
+        self.with(|objects| objects.get(&path)?.get(interface)?.get(name).cloned())
+            .await
+    }
+}
+
+/// Returns whether the object at `path` is `prefix` itself or one of its descendants.
+pub(crate) fn is_below(path: &str, prefix: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/'),
+        None => false,
+    }
+}
+
+impl Drop for ObjectCache {
+    fn drop(&mut self) {
+        // Let the background task notice that it should exit.
+        if let Some(waker) = self.lock().waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Keeps draining the signal streams until the [`ObjectCache`] is dropped.
+///
+/// zbus stops reading from the connection once a stream's queue is full, so the streams have to be
+/// drained even when no lookups are performed.
+async fn drive(cache: Weak<ObjectCache>) {
+    poll_fn(|cx| {
+        let Some(cache) = cache.upgrade() else {
+            return Poll::Ready(());
+        };
+        let mut state = cache.lock();
+        state.waker = Some(cx.waker().clone());
+        state.poll_signals(cx)
+    })
+    .await;
+    log::debug!("object cache task exiting");
+}
+
+impl State {
+    /// Applies all signals that have already been received.
+    ///
+    /// This makes any changes that BlueZ signaled before replying to a method call visible
+    /// immediately, without waiting for the background task to run.
+    fn drain(&mut self) {
+        // Polling with the background task's waker ensures that it still gets woken up.
+        if let Some(waker) = self.waker.clone() {
+            let _ = self.poll_signals(&mut Context::from_waker(&waker));
+        }
+    }
+
+    fn poll_signals(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(stream) = &mut self.owner_changes {
+            let mut messages = Vec::new();
+            let ended = poll_all(stream, cx, &mut messages);
+            for message in messages {
+                self.owner_changed(&message);
+            }
+            if ended {
+                return Poll::Ready(());
+            }
+        }
+
+        let mut messages = Vec::new();
+        let ended = poll_all(&mut self.signals, cx, &mut messages);
+        for message in messages {
+            self.apply(&message);
+        }
+        if ended {
+            log::debug!("signal stream ended");
+            return Poll::Ready(());
+        }
+
+        Poll::Pending
+    }
+
+    fn owner_changed(&mut self, message: &Message) {
+        let Ok((name, _, new_owner)) = message.body::<(String, String, String)>() else {
+            return;
+        };
+        if name != self.service.as_str() {
+            return;
+        }
+
+        log::debug!(
+            "owner of {} changed to '{}', invalidating cache",
+            name,
+            new_owner
+        );
+        self.owner = OwnedUniqueName::try_from(new_owner).ok();
+        self.generation += 1;
+        self.objects = None;
+        self.pending.clear();
+    }
+
+    fn apply(&mut self, message: &Arc<Message>) {
+        if self.is_bus {
+            let Ok(header) = message.header() else { return };
+            let sender = header.sender().ok().flatten();
+            if sender.is_none() || sender != self.owner.as_deref() {
+                return;
+            }
+        }
+
+        let Some(objects) = &mut self.objects else {
+            if self.fetching > 0 {
+                self.pending.push(message.clone());
+            }
+            return;
+        };
+
+        let (Some(interface), Some(member)) = (message.interface(), message.member()) else {
+            return;
+        };
+        match (interface.as_str(), member.as_str()) {
+            ("org.freedesktop.DBus.ObjectManager", "InterfacesAdded") => {
+                let Ok((path, added)) = message.body::<(OwnedObjectPath, Interfaces)>() else {
+                    return;
+                };
+                objects.entry(path).or_default().extend(added);
+            }
+            ("org.freedesktop.DBus.ObjectManager", "InterfacesRemoved") => {
+                let Ok((path, removed)) = message.body::<(OwnedObjectPath, Vec<String>)>() else {
+                    return;
+                };
+                if let Some(intfs) = objects.get_mut(&path) {
+                    for name in removed {
+                        intfs.remove(&name);
+                    }
+                    if intfs.is_empty() {
+                        objects.remove(&path);
+                    }
+                }
+            }
+            ("org.freedesktop.DBus.Properties", "PropertiesChanged") => {
+                let Some(path) = message.path() else { return };
+                let Ok((name, changed, invalidated)) =
+                    message.body::<(String, Properties, Vec<String>)>()
+                else {
+                    return;
+                };
+                let path = OwnedObjectPath::from(path.to_owned());
+                let Some(props) = objects
+                    .get_mut(&path)
+                    .and_then(|intfs| intfs.get_mut(&name))
+                else {
+                    return;
+                };
+                props.extend(changed);
+                for name in invalidated {
+                    props.remove(&name);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Polls `stream` until it is pending, collecting all messages.
+///
+/// Returns whether the stream has ended.
+fn poll_all(
+    stream: &mut MessageStream,
+    cx: &mut Context<'_>,
+    messages: &mut Vec<Arc<Message>>,
+) -> bool {
+    loop {
+        match Pin::new(&mut *stream).poll_next(cx) {
+            Poll::Ready(Some(Ok(message))) => messages.push(message),
+            Poll::Ready(Some(Err(e))) => log::warn!("error in signal stream: {}", e),
+            Poll::Ready(None) => return true,
+            Poll::Pending => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_prefix() {
+        assert!(is_below("/org/bluez/hci0", "/org/bluez/hci0"));
+        assert!(is_below("/org/bluez/hci0/dev_AA", "/org/bluez/hci0"));
+        assert!(is_below("/org/bluez/hci0", "/"));
+        assert!(!is_below("/org/bluez/hci10", "/org/bluez/hci1"));
+        assert!(!is_below("/org/bluez", "/org/bluez/hci0"));
+    }
+}
@@ -1,23 +1,33 @@
 //! BlueZ [`Device`] access.
 
 use core::fmt;
-use std::str::FromStr;
+use std::{
+    collections::HashMap, future::ready, hash::Hash, pin::pin, str::FromStr, time::Duration,
+};
 
-use futures_util::StreamExt;
+use futures_util::{
+    future::{select, Either},
+    stream, StreamExt,
+};
 use zbus::{
-    fdo::{PropertiesChangedStream, PropertiesProxy},
-    zvariant::ObjectPath,
+    fdo::{InterfacesRemovedStream, PropertiesChangedStream, PropertiesProxy},
+    zvariant::{ObjectPath, OwnedValue, Value},
+    PropertyStream,
 };
 
 use crate::{
     address::{Address, AddressType},
+    cache::is_below,
+    executor,
     gatt::Service,
     uuid::Uuid,
-    Error, Result, Session,
+    Error, ErrorKind, Result, Session,
 };
 
 mod private {
-    use zbus::dbus_proxy;
+    use std::collections::HashMap;
+
+    use zbus::{dbus_proxy, zvariant::OwnedValue};
 
     #[dbus_proxy(
         interface = "org.bluez.Device1",
@@ -27,6 +37,8 @@ mod private {
     trait Device {
         async fn connect(&self) -> zbus::Result<()>;
         async fn disconnect(&self) -> zbus::Result<()>;
+        async fn pair(&self) -> zbus::Result<()>;
+        async fn cancel_pairing(&self) -> zbus::Result<()>;
 
         #[dbus_proxy(property)]
         fn connected(&self) -> zbus::Result<bool>;
@@ -40,7 +52,7 @@ mod private {
         #[dbus_proxy(property)]
         fn alias(&self) -> zbus::Result<String>;
 
-        #[dbus_proxy(property)]
+        #[dbus_proxy(property, name = "RSSI")]
         fn rssi(&self) -> zbus::Result<i16>;
 
         #[dbus_proxy(property)]
@@ -48,6 +60,45 @@ mod private {
 
         #[dbus_proxy(property, name = "UUIDs")]
         fn uuids(&self) -> zbus::Result<Vec<String>>;
+
+        #[dbus_proxy(property)]
+        fn manufacturer_data(&self) -> zbus::Result<HashMap<u16, OwnedValue>>;
+
+        #[dbus_proxy(property)]
+        fn service_data(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+
+        #[dbus_proxy(property)]
+        fn tx_power(&self) -> zbus::Result<i16>;
+
+        #[dbus_proxy(property)]
+        fn advertising_flags(&self) -> zbus::Result<Vec<u8>>;
+
+        #[dbus_proxy(property)]
+        fn advertising_data(&self) -> zbus::Result<HashMap<u8, OwnedValue>>;
+
+        #[dbus_proxy(property)]
+        fn paired(&self) -> zbus::Result<bool>;
+
+        #[dbus_proxy(property)]
+        fn bonded(&self) -> zbus::Result<bool>;
+
+        #[dbus_proxy(property)]
+        fn trusted(&self) -> zbus::Result<bool>;
+        #[dbus_proxy(property)]
+        fn set_trusted(&self, trusted: bool) -> zbus::Result<()>;
+
+        #[dbus_proxy(property)]
+        fn blocked(&self) -> zbus::Result<bool>;
+        #[dbus_proxy(property)]
+        fn set_blocked(&self, blocked: bool) -> zbus::Result<()>;
+
+        #[dbus_proxy(property)]
+        fn legacy_pairing(&self) -> zbus::Result<bool>;
+
+        #[dbus_proxy(property)]
+        fn wake_allowed(&self) -> zbus::Result<bool>;
+        #[dbus_proxy(property)]
+        fn set_wake_allowed(&self, wake_allowed: bool) -> zbus::Result<()>;
     }
 }
 
@@ -55,7 +106,9 @@ use private::DeviceProxy;
 
 /// A reference to a remote BlueZ device.
 ///
-/// Instances of this type can be obtained from [`Adapter::device_stream`][crate::Adapter::device_stream].
+/// Instances of this type can be obtained from [`Adapter::device_stream`][crate::Adapter::device_stream],
+/// or for devices with a known address, via [`Adapter::device`][crate::Adapter::device] and
+/// [`Adapter::connect_device`][crate::Adapter::connect_device].
 #[derive(Clone)]
 pub struct Device {
     session: Session,
@@ -63,10 +116,15 @@ pub struct Device {
 }
 
 impl Device {
+    /// How often [`Device::connect`] attempts to connect before giving up.
+    const CONNECT_ATTEMPTS: u32 = 3;
+    /// How long [`Device::connect`] waits before its first retry. Doubled after every retry.
+    const CONNECT_BACKOFF: Duration = Duration::from_millis(100);
+
     pub(crate) async fn new(session: Session, path: ObjectPath<'static>) -> Result<Self> {
-        let proxy = DeviceProxy::new(&session.conn, path)
-            .await
-            .map_err(Error::from)?;
+        let proxy = session
+            .proxy(DeviceProxy::builder(&session.conn), path)
+            .await?;
         Ok(Self { session, proxy })
     }
 
@@ -76,24 +134,27 @@ impl Device {
 
     /// Returns the hardware [`Address`] of the device.
     pub async fn address(&self) -> Result<Address> {
-        let string = self.proxy.address().await.map_err(Error::from)?;
+        let string: String = self.session.property(self.proxy.inner(), "Address").await?;
         string.parse().map_err(Error::from)
     }
 
     /// Returns the type of the device's hardware [`Address`] returned by [`Device::address`].
     pub async fn address_type(&self) -> Result<AddressType> {
-        let string = self.proxy.address_type().await.map_err(Error::from)?;
+        let string: String = self
+            .session
+            .property(self.proxy.inner(), "AddressType")
+            .await?;
         AddressType::from_str(&string)
     }
 
     /// Returns the user-friendly name assigned to the device.
     pub async fn alias(&self) -> Result<String> {
-        self.proxy.alias().await.map_err(Error::from)
+        self.session.property(self.proxy.inner(), "Alias").await
     }
 
     /// Returns the Received Signal Strength Indicator (RSSI) of the remote device.
     pub async fn rssi(&self) -> Result<i16> {
-        self.proxy.rssi().await.map_err(Error::from)
+        self.session.property(self.proxy.inner(), "RSSI").await
     }
 
     /// Returns the list of service [`Uuid`]s the device is advertising.
@@ -101,13 +162,56 @@ impl Device {
     /// This list is available without performing full service discovery or connecting to the
     /// device, but is typically truncated unless connected to or paired with the [`Device`].
     pub async fn service_uuids(&self) -> Result<Vec<Uuid>> {
-        self.proxy
-            .uuids()
+        let uuids: Vec<String> = self.session.property(self.proxy.inner(), "UUIDs").await?;
+        uuids
+            .iter()
+            .map(|s| Uuid::from_str(s).map_err(Error::from))
+            .collect()
+    }
+
+    /// Returns the manufacturer-specific data the device is advertising, keyed by the Bluetooth SIG
+    /// company identifier.
+    pub async fn manufacturer_data(&self) -> Result<HashMap<u16, Vec<u8>>> {
+        let data: HashMap<u16, OwnedValue> = self
+            .session
+            .property(self.proxy.inner(), "ManufacturerData")
+            .await?;
+        decode_data(data)
+    }
+
+    /// Returns the service data the device is advertising, keyed by service [`Uuid`].
+    pub async fn service_data(&self) -> Result<HashMap<Uuid, Vec<u8>>> {
+        let data: HashMap<String, OwnedValue> = self
+            .session
+            .property(self.proxy.inner(), "ServiceData")
+            .await?;
+        decode_service_data(decode_data(data)?)
+    }
+
+    /// Returns the advertised transmission power level of the device (in dBm).
+    ///
+    /// Together with [`Device::rssi`], this can be used to estimate the path loss.
+    pub async fn tx_power(&self) -> Result<i16> {
+        self.session.property(self.proxy.inner(), "TxPower").await
+    }
+
+    /// Returns the raw Flags AD structure the device is advertising.
+    pub async fn advertising_flags(&self) -> Result<Vec<u8>> {
+        self.session
+            .property(self.proxy.inner(), "AdvertisingFlags")
             .await
-            .map_err(Error::from)?
-            .into_iter()
-            .map(|s| Uuid::from_str(&*s).map_err(Error::from))
-            .collect::<Result<Vec<_>>>()
+    }
+
+    /// Returns the raw advertising data of the device, keyed by AD type.
+    ///
+    /// BlueZ only includes AD types that it doesn't decode into other properties, and only when
+    /// running with experimental features enabled.
+    pub async fn advertising_data(&self) -> Result<HashMap<u8, Vec<u8>>> {
+        let data: HashMap<u8, OwnedValue> = self
+            .session
+            .property(self.proxy.inner(), "AdvertisingData")
+            .await?;
+        decode_data(data)
     }
 
     /// Performs service discovery on a connected [`Device`] and returns all offered GATT services.
@@ -122,20 +226,14 @@ impl Device {
         let mut services = Vec::new();
         let objects = self
             .session
-            .object_manager()
-            .await?
-            .get_managed_objects()
-            .await
-            .map_err(Error::from)?;
-        for (path, intf) in objects {
-            if path.starts_with(self.proxy.path().as_str())
-                && intf.contains_key("org.bluez.GattService1")
-            {
-                let res = Service::new(self.session.clone(), &path).await;
-                match res {
-                    Ok(service) => services.push(service),
-                    Err(e) => log::error!("skipping GATT service at {} due to error: {}", path, e),
-                }
+            .cache
+            .find(self.proxy.path().as_str(), "org.bluez.GattService1")
+            .await?;
+        for (path, _) in objects {
+            let res = Service::new(self.session.clone(), &path, self.path()).await;
+            match res {
+                Ok(service) => services.push(service),
+                Err(e) => log::error!("skipping GATT service at {} due to error: {}", path, e),
             }
         }
 
@@ -143,8 +241,9 @@ impl Device {
     }
 
     async fn wait_services_resolved(&self) -> Result<()> {
+        let mut watch = DisconnectWatch::new(&self.session, self.path(), self.path()).await?;
         if !self.is_connected().await? {
-            return Err(Error::from("device disconnected, cannot resolve services"));
+            return Err(Error::disconnected());
         }
 
         let mut stream = self.proxy.receive_services_resolved_changed().await;
@@ -154,26 +253,36 @@ impl Device {
         }
 
         log::debug!("waiting for services to be resolved");
-        while let Some(change) = stream.next().await {
-            // TODO: this can hang indefinitely when the connection dies.
-            // The DBus object disappears, so shouldn't the stream also end?
-            if change.get().await.map_err(Error::from)? {
-                log::debug!("service enumeration completed");
-                return Ok(());
+        let resolved = pin!(async {
+            while let Some(change) = stream.next().await {
+                if change.get().await.map_err(Error::from)? {
+                    log::debug!("service enumeration completed");
+                    return Ok(());
+                }
             }
-        }
 
-        // The stream ended. This may indicate that the device disappeared.
-        Err(Error::from("failed to resolve services"))
+            // The stream ended. This may indicate that the device disappeared.
+            Err(Error::from("failed to resolve services"))
+        });
+
+        let disconnected = pin!(watch.wait());
+        match select(resolved, disconnected).await {
+            Either::Left((res, _)) => res,
+            Either::Right((err, _)) => Err(err),
+        }
     }
 
     async fn services_resolved(&self) -> Result<bool> {
-        self.proxy.services_resolved().await.map_err(Error::from)
+        self.session
+            .property(self.proxy.inner(), "ServicesResolved")
+            .await
     }
 
     /// Establishes a connection to the device.
     ///
-    /// Does nothing if the adapter is already connected to the device.
+    /// Does nothing if the adapter is already connected to the device. Connection attempts that
+    /// fail due to transient link-layer errors are retried up to 2 times before returning an
+    /// error, waiting 100 ms before the first retry and 200 ms before the second.
     pub async fn connect(&self) -> Result<()> {
         // Connecting to a device we're already connected to can result in a cryptic
         // `le-connection-abort-by-local` error, so ensure that this call succeeds if the device is
@@ -182,15 +291,40 @@ impl Device {
             return Ok(());
         }
 
-        match self.proxy.connect().await {
-            Ok(()) => Ok(()),
-            Err(e) => {
-                // Connecting is racy, so check if we ended up connecting if it fails.
-                if let Ok(true) = self.is_connected().await {
-                    return Ok(());
-                }
-                return Err(Error::from(e));
+        let mut attempt = 1;
+        let mut backoff = Self::CONNECT_BACKOFF;
+        loop {
+            let e = match self.proxy.connect().await {
+                Ok(()) => return Ok(()),
+                Err(e) => Error::from(e),
+            };
+
+            // Connecting is racy, so check if we ended up connecting if it fails.
+            if e.kind() == ErrorKind::AlreadyConnected {
+                return Ok(());
+            }
+            if let Ok(true) = self.is_connected().await {
+                return Ok(());
+            }
+
+            // Link-layer connection failures (eg. `le-connection-abort-by-local`) are often
+            // transient and resolved by trying again.
+            let transient = matches!(
+                e.kind(),
+                ErrorKind::ConnectionFailed | ErrorKind::ConnectionAttemptFailed
+            );
+            if !transient || attempt == Self::CONNECT_ATTEMPTS {
+                return Err(e);
             }
+            log::debug!(
+                "connection attempt {} failed: {} (retrying in {:?})",
+                attempt,
+                e,
+                backoff
+            );
+            executor::sleep(backoff).await;
+            backoff *= 2;
+            attempt += 1;
         }
     }
 
@@ -205,22 +339,123 @@ impl Device {
         match self.proxy.disconnect().await {
             Ok(()) => Ok(()),
             Err(e) => {
+                let e = Error::from(e);
+                if e.kind() == ErrorKind::NotConnected {
+                    return Ok(());
+                }
                 if let Ok(false) = self.is_connected().await {
                     return Ok(());
                 }
-                return Err(Error::from(e));
+                Err(e)
             }
         }
     }
 
     /// Returns whether the adapter is currently connected to this device.
     pub async fn is_connected(&self) -> Result<bool> {
-        self.proxy.connected().await.map_err(Error::from)
+        self.session.property(self.proxy.inner(), "Connected").await
+    }
+
+    /// Pairs with the device.
+    ///
+    /// Depending on the IO capabilities of both sides, pairing may require user interaction (eg.
+    /// entering or confirming a passkey). This is handled by the registered pairing agent; without
+    /// one, only pairing methods that require no interaction ("Just Works") can succeed.
+    ///
+    /// Does nothing if the device is already paired.
+    pub async fn pair(&self) -> Result<()> {
+        if self.is_paired().await? {
+            return Ok(());
+        }
+
+        match self.proxy.pair().await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                // Pairing may have been completed by someone else in the meantime.
+                let e = Error::from(e);
+                if e.kind() == ErrorKind::AlreadyExists {
+                    return Ok(());
+                }
+                if let Ok(true) = self.is_paired().await {
+                    return Ok(());
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Cancels an in-progress pairing attempt started by [`Device::pair`].
+    pub async fn cancel_pairing(&self) -> Result<()> {
+        self.proxy.cancel_pairing().await.map_err(Error::from)
+    }
+
+    /// Returns whether the device is paired.
+    pub async fn is_paired(&self) -> Result<bool> {
+        self.session.property(self.proxy.inner(), "Paired").await
+    }
+
+    /// Returns whether the device is bonded, ie. whether the keys exchanged during pairing have
+    /// been stored persistently.
+    ///
+    /// This property is only available with BlueZ 5.68 or later.
+    pub async fn is_bonded(&self) -> Result<bool> {
+        self.session.property(self.proxy.inner(), "Bonded").await
+    }
+
+    /// Returns whether the device is trusted.
+    ///
+    /// Trusted devices may connect and use services without requiring authorization.
+    pub async fn is_trusted(&self) -> Result<bool> {
+        self.session.property(self.proxy.inner(), "Trusted").await
+    }
+
+    /// Marks the device as trusted or untrusted.
+    pub async fn set_trusted(&self, trusted: bool) -> Result<()> {
+        self.proxy.set_trusted(trusted).await.map_err(Error::from)
+    }
+
+    /// Returns whether the device is blocked.
+    ///
+    /// Incoming connections from blocked devices are rejected.
+    pub async fn is_blocked(&self) -> Result<bool> {
+        self.session.property(self.proxy.inner(), "Blocked").await
+    }
+
+    /// Blocks or unblocks the device.
+    ///
+    /// Blocking a device disconnects it.
+    pub async fn set_blocked(&self, blocked: bool) -> Result<()> {
+        self.proxy.set_blocked(blocked).await.map_err(Error::from)
+    }
+
+    /// Returns whether the device only supports the pre-2.1 legacy pairing mechanism (PIN codes).
+    pub async fn is_legacy_pairing(&self) -> Result<bool> {
+        self.session
+            .property(self.proxy.inner(), "LegacyPairing")
+            .await
+    }
+
+    /// Returns whether the device is allowed to wake up the host from system suspend.
+    pub async fn is_wake_allowed(&self) -> Result<bool> {
+        self.session
+            .property(self.proxy.inner(), "WakeAllowed")
+            .await
+    }
+
+    /// Sets whether the device is allowed to wake up the host from system suspend.
+    pub async fn set_wake_allowed(&self, wake_allowed: bool) -> Result<()> {
+        self.proxy
+            .set_wake_allowed(wake_allowed)
+            .await
+            .map_err(Error::from)
     }
 
     /// Returns a [`Changes`] stream that yields the [`PropertyName`] of properties when their
     /// values change.
     ///
+    /// The new values can be obtained along with the change notifications via
+    /// [`Changes::next_change`].
+    ///
     /// Only the [`PropertyName`]s passed as an argument will be subscribed to and yielded by the
     /// [`Changes`] stream.
     pub async fn property_change_stream<I: IntoIterator<Item = PropertyName>>(
@@ -234,14 +469,10 @@ impl Device {
     async fn property_change_stream_impl(&self, interest: Vec<PropertyName>) -> Result<Changes> {
         // Property changes are signaled via the `PropertiesChanged` signal on the
         // `org.freedesktop.DBus.Properties` interface.
-        let proxy = PropertiesProxy::builder(&self.session.conn)
-            .path(self.proxy.path())
-            .map_err(Error::from)?
-            .destination("org.bluez")
-            .map_err(Error::from)?
-            .build()
-            .await
-            .map_err(Error::from)?;
+        let proxy = self
+            .session
+            .proxy(PropertiesProxy::builder(&self.session.conn), self.path())
+            .await?;
         let stream = proxy
             .receive_properties_changed()
             .await
@@ -254,6 +485,89 @@ impl Device {
     }
 }
 
+/// Decodes one of the advertising data dictionaries (`ManufacturerData`, `ServiceData` and
+/// `AdvertisingData`).
+///
+/// BlueZ sends them as `a{qv}`, `a{sv}` and `a{yv}`, with each byte array wrapped in a variant.
+fn decode_data<K: Eq + Hash>(data: HashMap<K, OwnedValue>) -> Result<HashMap<K, Vec<u8>>> {
+    data.into_iter()
+        .map(|(key, value)| {
+            let mut value: &Value<'_> = &value;
+            while let Value::Value(inner) = value {
+                value = inner;
+            }
+            let bytes = Vec::try_from(value.clone()).map_err(zbus::Error::from)?;
+            Ok((key, bytes))
+        })
+        .collect::<zbus::Result<_>>()
+        .map_err(Error::from)
+}
+
+fn decode_service_data(data: HashMap<String, Vec<u8>>) -> Result<HashMap<Uuid, Vec<u8>>> {
+    data.into_iter()
+        .map(|(uuid, data)| Ok((uuid.parse().map_err(Error::from)?, data)))
+        .collect()
+}
+
+/// Watches for the disconnection of a [`Device`], or the removal of one of its D-Bus objects.
+pub(crate) struct DisconnectWatch {
+    connected: PropertyStream<'static, bool>,
+    removed: InterfacesRemovedStream<'static>,
+    object: ObjectPath<'static>,
+}
+
+impl DisconnectWatch {
+    /// Creates a [`DisconnectWatch`] for the [`Device`] at `device`.
+    ///
+    /// `object` is the object the caller is interested in (the device itself, or a GATT object
+    /// below it). Its removal, or the removal of any of its parents, is treated as a disconnect.
+    pub(crate) async fn new(
+        session: &Session,
+        device: ObjectPath<'static>,
+        object: ObjectPath<'static>,
+    ) -> Result<Self> {
+        let proxy = session
+            .proxy(DeviceProxy::builder(&session.conn), device)
+            .await?;
+        let connected = proxy.receive_connected_changed().await;
+        let removed = session
+            .object_manager()
+            .await?
+            .receive_interfaces_removed()
+            .await
+            .map_err(Error::from)?;
+        Ok(Self {
+            connected,
+            removed,
+            object,
+        })
+    }
+
+    /// Waits until the [`Device`] disconnects or the watched object disappears, and returns the
+    /// error to report to the caller.
+    pub(crate) async fn wait(&mut self) -> Error {
+        let object = self.object.as_str();
+        let disconnected = self.connected.by_ref().filter_map(|change| async move {
+            // Failing to fetch the new value most likely means that the device is gone.
+            match change.get().await {
+                Ok(true) => None,
+                Ok(false) | Err(_) => Some(()),
+            }
+        });
+        let removed = self.removed.by_ref().filter_map(|signal| {
+            let hit = signal
+                .args()
+                .is_ok_and(|args| is_below(object, &args.object_path));
+            ready(hit.then_some(()))
+        });
+
+        // If both streams end, the bus connection is gone, which we also treat as a disconnect.
+        pin!(stream::select(disconnected, removed)).next().await;
+        log::debug!("{} disconnected", object);
+        Error::disconnected()
+    }
+}
+
 impl fmt::Debug for Device {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Device")
@@ -266,7 +580,7 @@ impl fmt::Debug for Device {
 pub struct Changes {
     stream: PropertiesChangedStream<'static>,
     interest: Vec<PropertyName>,
-    change_buffer: Vec<PropertyName>,
+    change_buffer: Vec<PropertyChange>,
 }
 
 impl Changes {
@@ -283,6 +597,17 @@ impl Changes {
     /// other communication error. In general, the caller should assume that the stream is no longer
     /// operable if that happens.
     pub async fn wait(&mut self) -> Result<PropertyName> {
+        self.next_change().await.map(|change| change.name())
+    }
+
+    /// Like [`Changes::wait`], but returns a [`PropertyChange`] carrying the new value of the
+    /// property, as transmitted by BlueZ.
+    ///
+    /// This avoids having to fetch the new value separately, which would cost another D-Bus
+    /// round-trip and could observe a later value than the one that triggered the change.
+    /// Properties that BlueZ reports as invalidated (without transmitting a new value) are yielded
+    /// as [`PropertyChange::Invalidated`].
+    pub async fn next_change(&mut self) -> Result<PropertyChange> {
         if let Some(change) = self.change_buffer.pop() {
             return Ok(change);
         }
@@ -298,17 +623,21 @@ impl Changes {
                         args.changed_properties.keys(),
                     );
 
-                    for prop in args.changed_properties.keys() {
+                    if args.interface_name != "org.bluez.Device1" {
+                        continue;
+                    }
+
+                    for (prop, value) in &args.changed_properties {
                         if let Some(name) = PropertyName::from_str(prop) {
                             if self.interest.contains(&name) {
-                                self.change_buffer.push(name);
+                                self.change_buffer.push(PropertyChange::decode(name, value));
                             }
                         }
                     }
                     for prop in &args.invalidated_properties {
                         if let Some(name) = PropertyName::from_str(prop) {
                             if self.interest.contains(&name) {
-                                self.change_buffer.push(name);
+                                self.change_buffer.push(PropertyChange::Invalidated(name));
                             }
                         }
                     }
@@ -323,6 +652,92 @@ impl Changes {
     }
 }
 
+/// A change to a [`Device`] property, carrying the property's new value.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum PropertyChange {
+    /// [`Device::alias`] changed.
+    Alias(String),
+    /// [`Device::rssi`] changed.
+    Rssi(i16),
+    /// [`Device::service_uuids`] changed.
+    ServiceUuids(Vec<Uuid>),
+    /// [`Device::is_connected`] changed.
+    IsConnected(bool),
+    /// [`Device::manufacturer_data`] changed.
+    ManufacturerData(HashMap<u16, Vec<u8>>),
+    /// [`Device::service_data`] changed.
+    ServiceData(HashMap<Uuid, Vec<u8>>),
+    /// [`Device::tx_power`] changed.
+    TxPower(i16),
+    /// [`Device::advertising_flags`] changed.
+    AdvertisingFlags(Vec<u8>),
+    /// [`Device::advertising_data`] changed.
+    AdvertisingData(HashMap<u8, Vec<u8>>),
+    /// The property was invalidated, or its new value could not be decoded.
+    ///
+    /// The new value has to be fetched via the corresponding method on [`Device`].
+    Invalidated(PropertyName),
+}
+
+impl PropertyChange {
+    /// Returns the [`PropertyName`] of the property that changed.
+    pub fn name(&self) -> PropertyName {
+        match self {
+            Self::Alias(_) => PropertyName::Alias,
+            Self::Rssi(_) => PropertyName::Rssi,
+            Self::ServiceUuids(_) => PropertyName::ServiceUuids,
+            Self::IsConnected(_) => PropertyName::IsConnected,
+            Self::ManufacturerData(_) => PropertyName::ManufacturerData,
+            Self::ServiceData(_) => PropertyName::ServiceData,
+            Self::TxPower(_) => PropertyName::TxPower,
+            Self::AdvertisingFlags(_) => PropertyName::AdvertisingFlags,
+            Self::AdvertisingData(_) => PropertyName::AdvertisingData,
+            Self::Invalidated(name) => *name,
+        }
+    }
+
+    fn decode(name: PropertyName, value: &Value<'_>) -> Self {
+        let change = match (name, value) {
+            (PropertyName::Alias, Value::Str(s)) => Some(Self::Alias(s.to_string())),
+            (PropertyName::Rssi, Value::I16(rssi)) => Some(Self::Rssi(*rssi)),
+            (PropertyName::IsConnected, Value::Bool(b)) => Some(Self::IsConnected(*b)),
+            (PropertyName::ServiceUuids, Value::Array(array)) => array
+                .get()
+                .iter()
+                .map(|value| match value {
+                    Value::Str(s) => s.parse().ok(),
+                    _ => None,
+                })
+                .collect::<Option<Vec<Uuid>>>()
+                .map(Self::ServiceUuids),
+            (PropertyName::ManufacturerData, Value::Dict(dict)) => HashMap::try_from(dict.clone())
+                .ok()
+                .and_then(|data| decode_data(data).ok())
+                .map(Self::ManufacturerData),
+            (PropertyName::ServiceData, Value::Dict(dict)) => HashMap::try_from(dict.clone())
+                .ok()
+                .and_then(|data| decode_data(data).ok())
+                .and_then(|data| decode_service_data(data).ok())
+                .map(Self::ServiceData),
+            (PropertyName::TxPower, Value::I16(tx_power)) => Some(Self::TxPower(*tx_power)),
+            (PropertyName::AdvertisingFlags, Value::Array(array)) => Vec::try_from(array.clone())
+                .ok()
+                .map(Self::AdvertisingFlags),
+            (PropertyName::AdvertisingData, Value::Dict(dict)) => HashMap::try_from(dict.clone())
+                .ok()
+                .and_then(|data| decode_data(data).ok())
+                .map(Self::AdvertisingData),
+            _ => None,
+        };
+
+        change.unwrap_or_else(|| {
+            log::warn!("failed to decode value of {:?}: {:?}", name, value);
+            Self::Invalidated(name)
+        })
+    }
+}
+
 /// Identifies a [`Device`] property by name.
 ///
 /// A property's value can be fetched via the methods on [`Device`].
@@ -337,6 +752,16 @@ pub enum PropertyName {
     ServiceUuids,
     /// [`Device::is_connected`]. This allows detecting device disconnects.
     IsConnected,
+    /// [`Device::manufacturer_data`].
+    ManufacturerData,
+    /// [`Device::service_data`].
+    ServiceData,
+    /// [`Device::tx_power`].
+    TxPower,
+    /// [`Device::advertising_flags`].
+    AdvertisingFlags,
+    /// [`Device::advertising_data`].
+    AdvertisingData,
 }
 
 impl PropertyName {
@@ -346,7 +771,42 @@ impl PropertyName {
             "RSSI" => Self::Rssi,
             "UUIDs" => Self::ServiceUuids,
             "Connected" => Self::IsConnected,
+            "ManufacturerData" => Self::ManufacturerData,
+            "ServiceData" => Self::ServiceData,
+            "TxPower" => Self::TxPower,
+            "AdvertisingFlags" => Self::AdvertisingFlags,
+            "AdvertisingData" => Self::AdvertisingData,
             _ => return None,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_property_change() {
+        assert_eq!(
+            PropertyChange::decode(PropertyName::Rssi, &Value::I16(-60)),
+            PropertyChange::Rssi(-60),
+        );
+        assert_eq!(
+            PropertyChange::decode(PropertyName::IsConnected, &Value::Bool(true)),
+            PropertyChange::IsConnected(true),
+        );
+
+        // BlueZ wraps each byte array in a variant (`a{qv}`).
+        let data = HashMap::from([(0x004c_u16, Value::new(Value::from(vec![1_u8, 2, 3])))]);
+        assert_eq!(
+            PropertyChange::decode(PropertyName::ManufacturerData, &Value::from(data)),
+            PropertyChange::ManufacturerData(HashMap::from([(0x004c, vec![1, 2, 3])])),
+        );
+
+        // Mismatched types are reported as invalidated.
+        assert_eq!(
+            PropertyChange::decode(PropertyName::Rssi, &Value::Bool(true)),
+            PropertyChange::Invalidated(PropertyName::Rssi),
+        );
+    }
+}
@@ -1,4 +1,6 @@
-use std::fmt;
+use std::{fmt, io};
+
+use zbus::DBusError;
 
 use crate::{address::ParseAddressError, uuid::ParseUuidError};
 
@@ -8,70 +10,326 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// The primary error type used throughout this library.
 #[derive(Debug)]
 pub struct Error {
-    inner: ErrorKind,
+    inner: Repr,
+    kind: ErrorKind,
+    /// The D-Bus error name and message, if this error was returned by a D-Bus method call.
+    dbus: Option<Box<(String, Option<String>)>>,
 }
 
 impl Error {
-    pub(crate) fn from(e: impl Into<ErrorKind>) -> Self {
-        Self { inner: e.into() }
+    pub(crate) fn from(e: impl Into<Repr>) -> Self {
+        let inner = e.into();
+        let dbus = inner.dbus_error().map(Box::new);
+        let kind = match (&inner, dbus.as_deref()) {
+            (_, Some((name, message))) => ErrorKind::from_dbus(name, message.as_deref()),
+            (Repr::Io(_), None) => ErrorKind::Io,
+            (Repr::ParseAddressError(_) | Repr::ParseUuidError(_), None) => ErrorKind::InvalidData,
+            (Repr::Disconnected, None) => ErrorKind::Disconnected,
+            (Repr::Timeout(_), None) => ErrorKind::Timeout,
+            (Repr::Zbus(_) | Repr::Fdo(_) | Repr::Other(_), None) => ErrorKind::Other,
+        };
+        Self { inner, kind, dbus }
+    }
+
+    pub(crate) fn disconnected() -> Self {
+        Self::from(Repr::Disconnected)
+    }
+
+    pub(crate) fn timeout(what: &str) -> Self {
+        Self::from(Repr::Timeout(what.to_string()))
+    }
+
+    /// Returns the [`ErrorKind`] describing the cause of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the D-Bus error name (eg. `org.bluez.Error.InProgress`), if this error was returned
+    /// by BlueZ (or the D-Bus daemon).
+    pub fn dbus_name(&self) -> Option<&str> {
+        self.dbus.as_deref().map(|(name, _)| &**name)
+    }
+
+    /// Returns the error message attached to the D-Bus error, if any.
+    ///
+    /// For connection failures, BlueZ uses this to describe the reason for the failure (eg.
+    /// `le-connection-abort-by-local`).
+    pub fn dbus_message(&self) -> Option<&str> {
+        self.dbus
+            .as_deref()
+            .and_then(|(_, message)| message.as_deref())
+    }
+
+    /// Returns a [`bool`] indicating whether this error was caused by the remote device
+    /// disconnecting (or disappearing altogether).
+    ///
+    /// This includes calls to D-Bus objects that no longer exist, since BlueZ removes the objects
+    /// of a device's GATT database when it disconnects, and those of the device when it goes away.
+    pub fn is_disconnected(&self) -> bool {
+        matches!(self.kind, ErrorKind::Disconnected | ErrorKind::NotConnected)
+            || self.dbus_name() == Some("org.freedesktop.DBus.Error.UnknownObject")
+    }
+
+    /// Returns a [`bool`] indicating whether the failed operation might succeed when retried.
+    ///
+    /// This is the case for errors caused by transient conditions, like another operation being
+    /// in progress, or a connection attempt being aborted or timing out.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind,
+            ErrorKind::InProgress
+                | ErrorKind::NotReady
+                | ErrorKind::ConnectionFailed
+                | ErrorKind::ConnectionAttemptFailed
+                | ErrorKind::AuthenticationTimeout
+                | ErrorKind::Timeout
+        )
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.inner {
-            ErrorKind::Zbus(e) => e.fmt(f),
-            ErrorKind::Fdo(e) => e.fmt(f),
-            ErrorKind::ParseAddressError(e) => e.fmt(f),
-            ErrorKind::ParseUuidError(e) => e.fmt(f),
-            ErrorKind::Other(e) => e.fmt(f),
+            Repr::Zbus(e) => e.fmt(f),
+            Repr::Fdo(e) => e.fmt(f),
+            Repr::Io(e) => e.fmt(f),
+            Repr::ParseAddressError(e) => e.fmt(f),
+            Repr::ParseUuidError(e) => e.fmt(f),
+            Repr::Disconnected => f.write_str("device disconnected"),
+            Repr::Timeout(what) => write!(f, "timed out waiting for {}", what),
+            Repr::Other(e) => e.fmt(f),
         }
     }
 }
 
 impl std::error::Error for Error {}
 
+/// Describes the cause of an [`Error`].
+///
+/// Errors returned by BlueZ are classified based on their D-Bus error name. The raw name and
+/// message are available via [`Error::dbus_name`] and [`Error::dbus_message`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The operation failed for an unspecified reason (`org.bluez.Error.Failed`).
+    Failed,
+    /// Another operation of the same kind is already in progress (`org.bluez.Error.InProgress`).
+    InProgress,
+    /// The object or state to be created already exists (`org.bluez.Error.AlreadyExists`).
+    AlreadyExists,
+    /// The device is already connected (`org.bluez.Error.AlreadyConnected`).
+    AlreadyConnected,
+    /// The adapter is not ready, eg. because it is powered off (`org.bluez.Error.NotReady`).
+    NotReady,
+    /// The operation is not permitted (`org.bluez.Error.NotPermitted`).
+    NotPermitted,
+    /// The caller is not authorized to perform the operation (`org.bluez.Error.NotAuthorized`).
+    NotAuthorized,
+    /// The operation is not supported (`org.bluez.Error.NotSupported`).
+    NotSupported,
+    /// The requested resource is not available (`org.bluez.Error.NotAvailable`).
+    NotAvailable,
+    /// The device is not connected (`org.bluez.Error.NotConnected`).
+    NotConnected,
+    /// The object does not exist (`org.bluez.Error.DoesNotExist`).
+    DoesNotExist,
+    /// An argument passed to BlueZ was invalid (`org.bluez.Error.InvalidArguments` and friends).
+    InvalidArguments,
+    /// Authentication with the remote device failed (`org.bluez.Error.AuthenticationFailed`).
+    AuthenticationFailed,
+    /// Authentication was canceled (`org.bluez.Error.AuthenticationCanceled`).
+    AuthenticationCanceled,
+    /// Authentication was rejected (`org.bluez.Error.AuthenticationRejected`).
+    AuthenticationRejected,
+    /// Authentication timed out (`org.bluez.Error.AuthenticationTimeout`).
+    AuthenticationTimeout,
+    /// Establishing a connection failed (`org.bluez.Error.ConnectionAttemptFailed`).
+    ConnectionAttemptFailed,
+    /// A connection attempt was aborted or failed at the link layer.
+    ///
+    /// BlueZ reports this as `org.bluez.Error.Failed` with a message describing the reason (eg.
+    /// `le-connection-abort-by-local` or `br-connection-page-timeout`).
+    ConnectionFailed,
+    /// The request was rejected (`org.bluez.Error.Rejected`).
+    Rejected,
+    /// The request was canceled (`org.bluez.Error.Canceled`).
+    Canceled,
+    /// A D-Bus call did not receive a reply in time, or an operation did not complete in time.
+    Timeout,
+    /// The remote device disconnected or disappeared.
+    Disconnected,
+    /// An I/O error occurred.
+    Io,
+    /// Data received from BlueZ or the remote device could not be parsed.
+    InvalidData,
+    /// Any other error.
+    Other,
+}
+
+impl ErrorKind {
+    fn from_dbus(name: &str, message: Option<&str>) -> Self {
+        match name {
+            "org.bluez.Error.Failed" => match message {
+                Some(msg)
+                    if msg.starts_with("le-connection-") || msg.starts_with("br-connection-") =>
+                {
+                    Self::ConnectionFailed
+                }
+                _ => Self::Failed,
+            },
+            "org.bluez.Error.InProgress" => Self::InProgress,
+            "org.bluez.Error.AlreadyExists" => Self::AlreadyExists,
+            "org.bluez.Error.AlreadyConnected" => Self::AlreadyConnected,
+            "org.bluez.Error.NotReady" => Self::NotReady,
+            "org.bluez.Error.NotPermitted" => Self::NotPermitted,
+            "org.bluez.Error.NotAuthorized" => Self::NotAuthorized,
+            "org.bluez.Error.NotSupported" => Self::NotSupported,
+            "org.bluez.Error.NotAvailable" => Self::NotAvailable,
+            "org.bluez.Error.NotConnected" => Self::NotConnected,
+            "org.bluez.Error.DoesNotExist" => Self::DoesNotExist,
+            "org.bluez.Error.InvalidArguments"
+            | "org.bluez.Error.InvalidValueLength"
+            | "org.bluez.Error.InvalidOffset"
+            | "org.freedesktop.DBus.Error.InvalidArgs" => Self::InvalidArguments,
+            "org.bluez.Error.AuthenticationFailed" => Self::AuthenticationFailed,
+            "org.bluez.Error.AuthenticationCanceled" => Self::AuthenticationCanceled,
+            "org.bluez.Error.AuthenticationRejected" => Self::AuthenticationRejected,
+            "org.bluez.Error.AuthenticationTimeout" => Self::AuthenticationTimeout,
+            "org.bluez.Error.ConnectionAttemptFailed" => Self::ConnectionAttemptFailed,
+            "org.bluez.Error.Rejected" => Self::Rejected,
+            "org.bluez.Error.Canceled" => Self::Canceled,
+            "org.freedesktop.DBus.Error.NoReply"
+            | "org.freedesktop.DBus.Error.Timeout"
+            | "org.freedesktop.DBus.Error.TimedOut" => Self::Timeout,
+            "org.freedesktop.DBus.Error.AccessDenied" => Self::NotAuthorized,
+            "org.freedesktop.DBus.Error.ServiceUnknown"
+            | "org.freedesktop.DBus.Error.UnknownObject"
+            | "org.freedesktop.DBus.Error.UnknownMethod"
+            | "org.freedesktop.DBus.Error.UnknownProperty" => Self::DoesNotExist,
+            _ => Self::Other,
+        }
+    }
+}
+
 #[derive(Debug)]
-pub(crate) enum ErrorKind {
+pub(crate) enum Repr {
     Zbus(zbus::Error),
     Fdo(zbus::fdo::Error),
+    Io(io::Error),
     ParseAddressError(ParseAddressError),
     ParseUuidError(ParseUuidError),
+    Disconnected,
+    Timeout(String),
     Other(String),
 }
 
-impl From<zbus::Error> for ErrorKind {
+impl Repr {
+    /// Extracts the D-Bus error name and message from a failed method call.
+    fn dbus_error(&self) -> Option<(String, Option<String>)> {
+        fn from_zbus(e: &zbus::Error) -> Option<(String, Option<String>)> {
+            match e {
+                zbus::Error::MethodError(name, message, _) => {
+                    Some((name.to_string(), message.clone()))
+                }
+                zbus::Error::FDO(e) => from_fdo(e),
+                _ => None,
+            }
+        }
+
+        fn from_fdo(e: &zbus::fdo::Error) -> Option<(String, Option<String>)> {
+            match e {
+                zbus::fdo::Error::ZBus(e) => from_zbus(e),
+                e => Some((e.name().to_string(), e.description().map(String::from))),
+            }
+        }
+
+        match self {
+            Self::Zbus(e) => from_zbus(e),
+            Self::Fdo(e) => from_fdo(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<zbus::Error> for Repr {
     fn from(value: zbus::Error) -> Self {
         Self::Zbus(value)
     }
 }
 
-impl From<zbus::fdo::Error> for ErrorKind {
+impl From<zbus::fdo::Error> for Repr {
     fn from(value: zbus::fdo::Error) -> Self {
         Self::Fdo(value)
     }
 }
 
-impl From<ParseAddressError> for ErrorKind {
+impl From<io::Error> for Repr {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<ParseAddressError> for Repr {
     fn from(value: ParseAddressError) -> Self {
         Self::ParseAddressError(value)
     }
 }
 
-impl From<ParseUuidError> for ErrorKind {
+impl From<ParseUuidError> for Repr {
     fn from(value: ParseUuidError) -> Self {
         Self::ParseUuidError(value)
     }
 }
 
-impl From<String> for ErrorKind {
+impl From<String> for Repr {
     fn from(value: String) -> Self {
         Self::Other(value)
     }
 }
 
-impl From<&str> for ErrorKind {
+impl From<&str> for Repr {
     fn from(value: &str) -> Self {
         Self::Other(value.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify() {
+        let kind = |name, message| ErrorKind::from_dbus(name, message);
+        assert_eq!(
+            kind("org.bluez.Error.InProgress", None),
+            ErrorKind::InProgress
+        );
+        assert_eq!(
+            kind(
+                "org.bluez.Error.Failed",
+                Some("le-connection-abort-by-local")
+            ),
+            ErrorKind::ConnectionFailed
+        );
+        assert_eq!(
+            kind("org.bluez.Error.Failed", Some("Operation failed")),
+            ErrorKind::Failed
+        );
+        assert_eq!(kind("com.example.Error", None), ErrorKind::Other);
+
+        let err = Error::from(zbus::fdo::Error::NoReply("no reply".into()));
+        assert_eq!(err.kind(), ErrorKind::Timeout);
+        assert_eq!(err.dbus_name(), Some("org.freedesktop.DBus.Error.NoReply"));
+        assert_eq!(err.dbus_message(), Some("no reply"));
+        assert!(err.is_retryable());
+
+        assert!(Error::disconnected().is_disconnected());
+
+        let err = Error::from(zbus::fdo::Error::UnknownObject("no such object".into()));
+        assert_eq!(err.kind(), ErrorKind::DoesNotExist);
+        assert!(err.is_disconnected());
+        let err = Error::from(zbus::fdo::Error::UnknownMethod("no such method".into()));
+        assert!(!err.is_disconnected());
+        assert_eq!(Error::from("oops").kind(), ErrorKind::Other);
+    }
+}
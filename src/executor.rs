@@ -0,0 +1,283 @@
+//! A minimal, runtime-agnostic executor for driving futures outside of the caller's async context.
+//!
+//! This is used where async work has to happen in a place where we can't `.await` (eg. in `Drop`
+//! implementations), and to drive the [`blocking`][crate::blocking] API. It also provides a timer
+//! that works with any async runtime.
+
+use std::{
+    future::{poll_fn, Future},
+    panic::{self, AssertUnwindSafe},
+    pin::{pin, Pin},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Condvar, Mutex, Once,
+    },
+    task::{Context, Poll, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures_util::{
+    future::{abortable, select, AbortHandle, Either},
+    stream::FuturesUnordered,
+    FutureExt, StreamExt,
+};
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Futures submitted via [`spawn`] or [`spawn_detached`] that the executor thread hasn't picked
+/// up yet.
+struct Incoming {
+    tasks: Vec<BoxFuture>,
+    waker: Option<Waker>,
+}
+
+static INCOMING: Mutex<Incoming> = Mutex::new(Incoming {
+    tasks: Vec::new(),
+    waker: None,
+});
+static START: Once = Once::new();
+
+/// Runs `future` to completion on the shared background thread, without waiting for it.
+pub(crate) fn spawn_detached<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    submit(Box::pin(async move {
+        // Don't let panics take down the shared thread.
+        if AssertUnwindSafe(future).catch_unwind().await.is_err() {
+            log::error!("background task panicked");
+        }
+    }));
+}
+
+/// Runs `future` on the shared background thread and returns a [`Task`] handle for its output.
+///
+/// Dropping the [`Task`] cancels the future.
+pub(crate) fn spawn<F>(future: F) -> Task<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let (future, abort) = abortable(future);
+    let (sender, output) = mpsc::sync_channel(1);
+    let task = async move {
+        // Catch panics so that they don't take down the shared thread, and forward them instead.
+        match AssertUnwindSafe(future).catch_unwind().await {
+            Ok(Ok(out)) => drop(sender.send(Ok(out))),
+            Ok(Err(_aborted)) => {}
+            Err(payload) => drop(sender.send(Err(payload))),
+        }
+    };
+
+    submit(Box::pin(task));
+
+    Task { output, abort }
+}
+
+fn submit(task: BoxFuture) {
+    START.call_once(|| {
+        let res = thread::Builder::new()
+            .name("blues-executor".into())
+            .spawn(run_executor);
+        if let Err(e) = res {
+            log::error!("failed to spawn executor thread: {}", e);
+        }
+    });
+
+    let mut incoming = INCOMING.lock().unwrap();
+    incoming.tasks.push(task);
+    if let Some(waker) = incoming.waker.take() {
+        waker.wake();
+    }
+}
+
+/// Runs the executor thread.
+#[cfg(not(feature = "tokio"))]
+fn run_executor() {
+    use std::{sync::Arc, task::Wake, thread::Thread};
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+
+        fn wake_by_ref(self: &Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let mut shared = pin!(run_shared());
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    while shared.as_mut().poll(&mut cx).is_pending() {
+        thread::park();
+    }
+}
+
+/// Runs the executor thread.
+///
+/// With the `tokio` feature, zbus has to be driven from within a tokio runtime (eg. to build
+/// connections for the `blocking` API), so the thread runs its own.
+#[cfg(feature = "tokio")]
+fn run_executor() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build();
+    match runtime {
+        Ok(runtime) => runtime.block_on(run_shared()),
+        Err(e) => log::error!("failed to start executor runtime: {}", e),
+    }
+}
+
+/// Polls all submitted futures, forever.
+async fn run_shared() {
+    let mut running = FuturesUnordered::new();
+    poll_fn(|cx| {
+        {
+            let mut incoming = INCOMING.lock().unwrap();
+            running.extend(incoming.tasks.drain(..));
+            incoming.waker = Some(cx.waker().clone());
+        }
+        // Returns `Ready(None)` once `running` is empty, `Pending` if no task could make progress.
+        while let Poll::Ready(Some(())) = running.poll_next_unpin(cx) {}
+        Poll::<()>::Pending
+    })
+    .await
+}
+
+/// A handle to a future running on the shared executor thread.
+///
+/// Returned by [`spawn`].
+pub(crate) struct Task<T> {
+    output: Receiver<thread::Result<T>>,
+    abort: AbortHandle,
+}
+
+impl<T> Task<T> {
+    /// Blocks the current thread until the future completes.
+    ///
+    /// If the future panicked, the panic is propagated to the caller.
+    pub(crate) fn wait(self) -> T {
+        match self.wait_timeout(None) {
+            Some(out) => out,
+            None => unreachable!(),
+        }
+    }
+
+    /// Blocks the current thread until the future completes or `timeout` elapses.
+    ///
+    /// Returns [`None`] if the timeout elapsed. The future keeps running in that case, and its
+    /// output can be retrieved by calling this method again.
+    pub(crate) fn wait_timeout(&self, timeout: Option<Duration>) -> Option<T> {
+        let res = match timeout {
+            Some(timeout) => match self.output.recv_timeout(timeout) {
+                Ok(res) => res,
+                Err(RecvTimeoutError::Timeout) => return None,
+                Err(RecvTimeoutError::Disconnected) => panic!("executor task vanished"),
+            },
+            None => self.output.recv().expect("executor task vanished"),
+        };
+        match res {
+            Ok(out) => Some(out),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+impl<T> Drop for Task<T> {
+    fn drop(&mut self) {
+        self.abort.abort();
+    }
+}
+
+/// Wakers of pending [`Sleep`] futures, and their deadlines.
+static TIMERS: Mutex<Vec<(Instant, Waker)>> = Mutex::new(Vec::new());
+static TIMERS_CHANGED: Condvar = Condvar::new();
+static TIMER_START: Once = Once::new();
+
+/// Returns a future that completes once `duration` has elapsed.
+pub(crate) fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        deadline: Instant::now() + duration,
+    }
+}
+
+/// Awaits `future`, or returns [`None`] if it doesn't complete within `duration`.
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    match select(pin!(future), sleep(duration)).await {
+        Either::Left((out, _)) => Some(out),
+        Either::Right(((), _)) => None,
+    }
+}
+
+/// Future returned by [`sleep`].
+pub(crate) struct Sleep {
+    deadline: Instant,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        TIMER_START.call_once(|| {
+            let res = thread::Builder::new()
+                .name("blues-timer".into())
+                .spawn(run_timers);
+            if let Err(e) = res {
+                log::error!("failed to spawn timer thread: {}", e);
+            }
+        });
+
+        // Stale entries from earlier polls just cause a spurious wakeup.
+        TIMERS
+            .lock()
+            .unwrap()
+            .push((self.deadline, cx.waker().clone()));
+        TIMERS_CHANGED.notify_one();
+        Poll::Pending
+    }
+}
+
+/// Wakes [`Sleep`] futures once their deadline has passed, forever.
+fn run_timers() {
+    let mut timers = TIMERS.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        timers.retain(|(deadline, waker)| {
+            if *deadline <= now {
+                waker.wake_by_ref();
+            }
+            *deadline > now
+        });
+
+        timers = match timers.iter().map(|(deadline, _)| *deadline).min() {
+            Some(next) => TIMERS_CHANGED.wait_timeout(timers, next - now).unwrap().0,
+            None => TIMERS_CHANGED.wait(timers).unwrap(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::{pending, ready};
+
+    use super::*;
+
+    #[test]
+    fn sleep_and_timeout() {
+        let start = Instant::now();
+        pollster::block_on(sleep(Duration::from_millis(20)));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let short = Duration::from_millis(10);
+        assert_eq!(pollster::block_on(timeout(short, ready(1))), Some(1));
+        assert_eq!(pollster::block_on(timeout(short, pending::<()>())), None);
+    }
+}
@@ -1,17 +1,33 @@
-//! GATT [`Service`]s and [`Characteristic`]s exported by BLE devices.
+//! GATT [`Service`]s, [`Characteristic`]s and [`Descriptor`]s exported by BLE devices.
 
-use futures_util::StreamExt;
+use std::{future::Future, pin::pin};
+
+use futures_util::{
+    future::{select, Either},
+    StreamExt,
+};
 use zbus::{
     zvariant::{ObjectPath, Value},
     PropertyStream,
 };
 
-use crate::{uuid::Uuid, Error, Result, Session};
+use crate::{
+    device::{Device, DisconnectWatch},
+    uuid::Uuid,
+    Error, Result, Session,
+};
+
+mod acquire;
+mod flags;
+mod format;
+
+pub use flags::{CharacteristicFlag, CharacteristicFlags, SecurityLevel};
+pub use format::{Format, PresentationFormat, ScaledValue};
 
 mod private {
     use zbus::{
         dbus_proxy,
-        zvariant::{ObjectPath, SerializeDict, Type},
+        zvariant::{ObjectPath, OwnedFd, SerializeDict, Type},
     };
 
     #[dbus_proxy(
@@ -39,6 +55,9 @@ mod private {
         fn start_notify(&self) -> zbus::Result<()>;
         fn stop_notify(&self) -> zbus::Result<()>;
 
+        fn acquire_notify(&self, options: &AcquireOptions) -> zbus::Result<(OwnedFd, u16)>;
+        fn acquire_write(&self, options: &AcquireOptions) -> zbus::Result<(OwnedFd, u16)>;
+
         #[dbus_proxy(property, name = "UUID")]
         fn uuid(&self) -> zbus::Result<String>;
 
@@ -52,14 +71,43 @@ mod private {
         fn mtu(&self) -> zbus::Result<u16>;
     }
 
-    #[derive(SerializeDict, Type)]
+    #[dbus_proxy(
+        interface = "org.bluez.GattDescriptor1",
+        default_service = "org.bluez",
+        assume_defaults = false
+    )]
+    trait GattDescriptor {
+        fn read_value(&self, options: &ReadOptions) -> zbus::Result<Vec<u8>>;
+        fn write_value(&self, value: &[u8], options: &WriteOptions) -> zbus::Result<()>;
+
+        #[dbus_proxy(property, name = "UUID")]
+        fn uuid(&self) -> zbus::Result<String>;
+
+        #[dbus_proxy(property)]
+        fn value(&self) -> zbus::Result<Vec<u8>>;
+
+        #[dbus_proxy(property)]
+        fn flags(&self) -> zbus::Result<Vec<String>>;
+    }
+
+    #[derive(Default, SerializeDict, Type)]
     #[zvariant(signature = "dict")]
     pub struct ReadOptions {
         // FIXME: `pub` because zbus' `dbus_proxy` macro *always* generates public proxy types and
         // methods instead of copying the trait visibility
-        offset: Option<u16>,
-        mtu: Option<u16>,
-        device: Option<ObjectPath<'static>>,
+        pub(super) offset: Option<u16>,
+        pub(super) mtu: Option<u16>,
+        pub(super) device: Option<ObjectPath<'static>>,
+    }
+
+    #[derive(Default, SerializeDict, Type)]
+    #[zvariant(signature = "dict")]
+    pub struct AcquireOptions {
+        // FIXME: `pub` because zbus' `dbus_proxy` macro *always* generates public proxy types and
+        // methods instead of copying the trait visibility
+        pub(super) mtu: Option<u16>,
+        pub(super) device: Option<ObjectPath<'static>>,
+        pub(super) link: Option<String>,
     }
 
     #[derive(Default, SerializeDict, Type)]
@@ -67,19 +115,22 @@ mod private {
     pub struct WriteOptions {
         // FIXME: `pub` because zbus' `dbus_proxy` macro *always* generates public proxy types and
         // methods instead of copying the trait visibility
-        offset: Option<u16>,
+        pub(super) offset: Option<u16>,
         /// `command`, `request`, `reliable`
         #[zvariant(rename = "type")]
-        ty: Option<&'static str>,
-        mtu: Option<u16>,
-        device: Option<ObjectPath<'static>>,
-        link: Option<String>,
+        pub(super) ty: Option<&'static str>,
+        pub(super) mtu: Option<u16>,
+        pub(super) device: Option<ObjectPath<'static>>,
+        pub(super) link: Option<String>,
         #[zvariant(rename = "prepare-authorize")]
-        prepare_authorize: Option<bool>,
+        pub(super) prepare_authorize: Option<bool>,
     }
 }
 
-use self::private::{GattCharacteristicProxy, GattServiceProxy, WriteOptions};
+use self::{
+    acquire::{NotifySocket, WriteSocket},
+    private::{AcquireOptions, GattCharacteristicProxy, GattDescriptorProxy, GattServiceProxy},
+};
 
 /// A GATT service of a Bluetooth LE device.
 ///
@@ -89,31 +140,35 @@ use self::private::{GattCharacteristicProxy, GattServiceProxy, WriteOptions};
 pub struct Service {
     proxy: GattServiceProxy<'static>,
     session: Session,
+    device: ObjectPath<'static>,
 }
 
 impl Service {
-    pub(crate) async fn new(session: Session, path: &ObjectPath<'static>) -> Result<Self> {
+    pub(crate) async fn new(
+        session: Session,
+        path: &ObjectPath<'static>,
+        device: ObjectPath<'static>,
+    ) -> Result<Self> {
         Ok(Self {
-            proxy: GattServiceProxy::new(&session.conn, path)
-                .await
-                .map_err(Error::from)?,
+            proxy: session
+                .proxy(GattServiceProxy::builder(&session.conn), path)
+                .await?,
             session,
+            device,
         })
     }
 
     /// Returns the [`Uuid`] identifying this [`Service`].
     pub async fn uuid(&self) -> Result<Uuid> {
-        match self.proxy.uuid().await {
-            Ok(uuid) => uuid.parse().map_err(Error::from),
-            Err(e) => Err(Error::from(e)),
-        }
+        let uuid: String = self.session.property(self.proxy.inner(), "UUID").await?;
+        uuid.parse().map_err(Error::from)
     }
 
     /// Returns a [`bool`] indicating whether this [`Service`] is a primary service.
     ///
     /// If `false`, the service is secondary.
     pub async fn is_primary(&self) -> Result<bool> {
-        self.proxy.primary().await.map_err(Error::from)
+        self.session.property(self.proxy.inner(), "Primary").await
     }
 
     /// Returns the [`Characteristic`] associated with this [`Service`] identified by the given
@@ -124,22 +179,15 @@ impl Service {
     pub async fn characteristic(&self, uuid: Uuid) -> Result<Characteristic> {
         let objects = self
             .session
-            .object_manager()
-            .await?
-            .get_managed_objects()
-            .await
-            .map_err(Error::from)?;
+            .cache
+            .find(self.proxy.path().as_str(), "org.bluez.GattCharacteristic1")
+            .await?;
 
         let value = Value::from(uuid.to_string());
-        for (path, intfs) in objects {
-            if !path.starts_with(self.proxy.path().as_str()) {
-                continue;
-            }
-
-            let Some(props) = intfs.get("org.bluez.GattCharacteristic1") else { continue };
+        for (path, props) in objects {
             let Some(s) = props.get("UUID") else { continue };
             if **s == value {
-                return Characteristic::new(&self.session, &path).await;
+                return Characteristic::new(self, &path).await;
             }
         }
 
@@ -153,19 +201,13 @@ impl Service {
     pub async fn characteristics(&self) -> Result<Vec<Characteristic>> {
         let objects = self
             .session
-            .object_manager()
-            .await?
-            .get_managed_objects()
-            .await
-            .map_err(Error::from)?;
+            .cache
+            .find(self.proxy.path().as_str(), "org.bluez.GattCharacteristic1")
+            .await?;
 
         let mut characteristics = Vec::new();
-        for (path, intfs) in objects {
-            if path.starts_with(self.proxy.path().as_str())
-                && intfs.contains_key("org.bluez.GattCharacteristic1")
-            {
-                characteristics.push(Characteristic::new(&self.session, &path).await?);
-            }
+        for (path, _) in objects {
+            characteristics.push(Characteristic::new(self, &path).await?);
         }
 
         Ok(characteristics)
@@ -178,17 +220,34 @@ impl Service {
 /// and/or written by the host.
 pub struct Characteristic {
     proxy: GattCharacteristicProxy<'static>,
+    session: Session,
+    device: ObjectPath<'static>,
 }
 
 impl Characteristic {
-    async fn new(session: &Session, path: &ObjectPath<'static>) -> Result<Self> {
+    async fn new(service: &Service, path: &ObjectPath<'static>) -> Result<Self> {
         Ok(Self {
-            proxy: GattCharacteristicProxy::new(&session.conn, path)
-                .await
-                .map_err(Error::from)?,
+            proxy: service
+                .session
+                .proxy(
+                    GattCharacteristicProxy::builder(&service.session.conn),
+                    path,
+                )
+                .await?,
+            session: service.session.clone(),
+            device: service.device.clone(),
         })
     }
 
+    async fn disconnect_watch(&self) -> Result<DisconnectWatch> {
+        DisconnectWatch::new(
+            &self.session,
+            self.device.clone(),
+            self.proxy.path().to_owned(),
+        )
+        .await
+    }
+
     /// Returns the [`Uuid`] identifying this [`Characteristic`].
     ///
     /// The returned [`Uuid`] determines the data format of the characteristic's value. For standard
@@ -196,91 +255,584 @@ impl Characteristic {
     /// their "Assigned Numbers" document. For vendor-specific characteristics, consult the vendor
     /// for documentation.
     pub async fn uuid(&self) -> Result<Uuid> {
-        match self.proxy.uuid().await {
-            Ok(s) => s.parse().map_err(Error::from),
-            Err(e) => Err(Error::from(e)),
-        }
+        let uuid: String = self.session.property(self.proxy.inner(), "UUID").await?;
+        uuid.parse().map_err(Error::from)
     }
 
     /// Returns the Maximum Transmission Unit (MTU) of this characteristic in Bytes.
     pub async fn mtu(&self) -> Result<u16> {
-        self.proxy.mtu().await.map_err(Error::from)
+        self.session.property(self.proxy.inner(), "MTU").await
     }
 
     /// Returns the [`CharacteristicFlags`] associated with this [`Characteristic`].
     ///
     /// These flags indicate which operations the [`Characteristic`] supports.
     pub async fn flags(&self) -> Result<CharacteristicFlags> {
-        self.proxy
-            .flags()
+        self.session
+            .property(self.proxy.inner(), "Flags")
             .await
-            .map_err(Error::from)
-            .map(|flags| CharacteristicFlags { flags })
+            .map(CharacteristicFlags::from_strings)
+    }
+
+    /// Returns the [`Descriptor`] of this [`Characteristic`] identified by the given [`Uuid`].
+    ///
+    /// Returns an error if the [`Characteristic`] does not have any [`Descriptor`] with the given
+    /// [`Uuid`].
+    pub async fn descriptor(&self, uuid: Uuid) -> Result<Descriptor> {
+        let objects = self
+            .session
+            .cache
+            .find(self.proxy.path().as_str(), "org.bluez.GattDescriptor1")
+            .await?;
+
+        let value = Value::from(uuid.to_string());
+        for (path, props) in objects {
+            let Some(s) = props.get("UUID") else { continue };
+            if **s == value {
+                return Descriptor::new(self, &path).await;
+            }
+        }
+
+        Err(Error::from(format!(
+            "no descriptor with UUID {} found in characteristic",
+            uuid
+        )))
+    }
+
+    /// Returns a list of all [`Descriptor`]s of this [`Characteristic`].
+    pub async fn descriptors(&self) -> Result<Vec<Descriptor>> {
+        let objects = self
+            .session
+            .cache
+            .find(self.proxy.path().as_str(), "org.bluez.GattDescriptor1")
+            .await?;
+
+        let mut descriptors = Vec::new();
+        for (path, _) in objects {
+            descriptors.push(Descriptor::new(self, &path).await?);
+        }
+
+        Ok(descriptors)
+    }
+
+    /// Reads and decodes the Characteristic Presentation Format [`Descriptor`] of this
+    /// [`Characteristic`].
+    ///
+    /// The returned [`PresentationFormat`] can be used to decode the raw value of the
+    /// [`Characteristic`] via [`PresentationFormat::decode`].
+    ///
+    /// Returns an error if the [`Characteristic`] does not have a presentation format descriptor.
+    pub async fn presentation_format(&self) -> Result<PresentationFormat> {
+        let desc = self.descriptor(PresentationFormat::UUID).await?;
+        PresentationFormat::from_bytes(&desc.read().await?)
     }
 
     /// Enables notifications/indications for this [`Characteristic`] and returns a [`ValueStream`]
     /// that will report changes to the [`Characteristic`]'s value.
+    ///
+    /// Notifications stay enabled until the last [`ValueStream`] for this [`Characteristic`] is
+    /// dropped or [unsubscribed][ValueStream::unsubscribe].
     pub async fn subscribe(&self) -> Result<ValueStream> {
-        self.proxy.start_notify().await.map_err(Error::from)?;
+        let watch = self.disconnect_watch().await?;
+        // Wait for the proxy's property cache to be populated first, otherwise populating it would
+        // be reported as a value change.
+        let _ = self.proxy.value().await;
         let stream = self.proxy.receive_value_changed().await;
-        Ok(ValueStream { stream })
+        let subscription = Subscription::start(&self.session, &self.proxy).await?;
+        Ok(ValueStream {
+            inner: ValueStreamKind::Property(stream, subscription),
+            watch,
+        })
+    }
+
+    /// Acquires a dedicated socket for receiving notifications from this [`Characteristic`] and
+    /// returns a [`ValueStream`] that reads from it.
+    ///
+    /// This bypasses the D-Bus signal that [`Characteristic::subscribe`] relies on, which makes it
+    /// suitable for high-rate notifications. Notifications delivered via the socket are never
+    /// coalesced.
+    ///
+    /// If the [`Characteristic`] does not support acquiring a notification socket, or the socket has
+    /// already been acquired, this falls back to [`Characteristic::subscribe`].
+    pub async fn acquire_notify(&self) -> Result<ValueStream> {
+        let watch = self.disconnect_watch().await?;
+        let res = self
+            .proxy
+            .acquire_notify(&AcquireOptions::default())
+            .await
+            .map_err(Error::from);
+        let (fd, mtu) = match res {
+            Ok(res) => res,
+            // Any error reported by BlueZ (eg. `NotSupported`, `NotPermitted` when the socket is
+            // already acquired, or `UnknownMethod` on old versions) can be worked around.
+            Err(e) if e.dbus_name().is_some() && !e.is_disconnected() => {
+                log::debug!(
+                    "AcquireNotify failed on {} ({}), falling back to StartNotify",
+                    self.proxy.path(),
+                    e
+                );
+                return self.subscribe().await;
+            }
+            Err(e) => return Err(e),
+        };
+        log::debug!("acquired notification socket (MTU={})", mtu);
+        let socket = NotifySocket::new(fd, mtu).map_err(Error::from)?;
+        Ok(ValueStream {
+            inner: ValueStreamKind::Acquired(socket),
+            watch,
+        })
+    }
+
+    /// Acquires a dedicated socket for writing to this [`Characteristic`] without response, and
+    /// returns a [`ValueWriter`] that writes to it.
+    ///
+    /// This bypasses D-Bus for every individual write, which allows for much higher throughput
+    /// than [`Characteristic::write`].
+    ///
+    /// If the [`Characteristic`] does not support acquiring a write socket, or the socket has already
+    /// been acquired, the returned [`ValueWriter`] falls back to performing [`WriteType::Command`] writes via D-Bus.
+    pub async fn acquire_write(&self) -> Result<ValueWriter> {
+        let res = self
+            .proxy
+            .acquire_write(&AcquireOptions::default())
+            .await
+            .map_err(Error::from);
+        let (fd, mtu) = match res {
+            Ok(res) => res,
+            // Like in `acquire_notify`, any error reported by BlueZ can be worked around.
+            Err(e) if e.dbus_name().is_some() && !e.is_disconnected() => {
+                log::debug!(
+                    "AcquireWrite failed on {} ({}), falling back to WriteValue",
+                    self.proxy.path(),
+                    e
+                );
+                return Ok(ValueWriter {
+                    mtu: self.mtu().await?,
+                    inner: ValueWriterKind::Fallback(self.proxy.clone()),
+                });
+            }
+            Err(e) => return Err(e),
+        };
+        log::debug!("acquired write socket (MTU={})", mtu);
+        let socket = WriteSocket::new(fd).map_err(Error::from)?;
+        Ok(ValueWriter {
+            mtu,
+            inner: ValueWriterKind::Acquired(socket),
+        })
+    }
+
+    /// Reads the current value of this [`Characteristic`] from the device.
+    ///
+    /// This is equivalent to calling [`Characteristic::read_with`] with the default
+    /// [`ReadOptions`].
+    pub async fn read(&self) -> Result<Vec<u8>> {
+        self.read_with(&ReadOptions::new()).await
+    }
+
+    /// Reads the value of this [`Characteristic`] using the given [`ReadOptions`].
+    ///
+    /// If [`ReadOptions::read_long`] is enabled, this will keep reading at increasing offsets until
+    /// the full attribute value has been assembled.
+    pub async fn read_with(&self, options: &ReadOptions) -> Result<Vec<u8>> {
+        let mtu = match (options.long, options.mtu) {
+            (false, _) => None,
+            (true, Some(mtu)) => Some(mtu),
+            (true, None) => Some(self.mtu().await?),
+        };
+        read_value(options, mtu, |opts| async move {
+            self.proxy.read_value(&opts).await.map_err(Error::from)
+        })
+        .await
     }
 
     /// Writes a new value to this [`Characteristic`].
+    ///
+    /// BlueZ chooses the [`WriteType`] based on the [`CharacteristicFlags`] of the
+    /// [`Characteristic`] (see [`CharacteristicFlags::default_write_type`]).
     pub async fn write(&self, value: &[u8]) -> Result<()> {
+        self.write_with(value, &WriteOptions::new()).await
+    }
+
+    /// Writes a new value to this [`Characteristic`] using the given [`WriteOptions`].
+    ///
+    /// If no [`WriteType`] was set via [`WriteOptions::write_type`], BlueZ chooses one based on the
+    /// [`CharacteristicFlags`] of the [`Characteristic`] (see
+    /// [`CharacteristicFlags::default_write_type`]).
+    pub async fn write_with(&self, value: &[u8], options: &WriteOptions) -> Result<()> {
+        let opts = private::WriteOptions {
+            offset: Some(options.offset),
+            ty: options.ty.map(WriteType::as_str),
+            mtu: options.mtu,
+            device: options.device.clone(),
+            link: None,
+            prepare_authorize: options.prepare_authorize.then_some(true),
+        };
         self.proxy
-            .write_value(value, &WriteOptions::default())
+            .write_value(value, &opts)
             .await
             .map_err(Error::from)
     }
 }
 
-/// A set of flags detailing the supported operations on a [`Characteristic`].
-#[derive(Debug)]
-pub struct CharacteristicFlags {
-    flags: Vec<String>,
+/// A descriptor of a [`Characteristic`].
+///
+/// Descriptors contain additional information about a [`Characteristic`] and its value, such as a
+/// human-readable description or the format of the value.
+///
+/// To enumerate [`Descriptor`]s, use [`Characteristic::descriptors`].
+pub struct Descriptor {
+    session: Session,
+    proxy: GattDescriptorProxy<'static>,
+    characteristic: GattCharacteristicProxy<'static>,
 }
 
-impl CharacteristicFlags {
-    /// Returns a [`bool`] indicating whether the device can notify the host of changes made to the
-    /// [`Characteristic`]'s value.
+impl Descriptor {
+    async fn new(characteristic: &Characteristic, path: &ObjectPath<'static>) -> Result<Self> {
+        Ok(Self {
+            proxy: characteristic
+                .session
+                .proxy(
+                    GattDescriptorProxy::builder(&characteristic.session.conn),
+                    path,
+                )
+                .await?,
+            session: characteristic.session.clone(),
+            characteristic: characteristic.proxy.clone(),
+        })
+    }
+
+    /// Returns the [`Uuid`] identifying this [`Descriptor`].
     ///
-    /// If this returns `true`, [`Characteristic::subscribe`] can be used to obtain a
-    /// [`ValueStream`] that reports every notification.
-    pub fn can_notify(&self) -> bool {
-        self.flags.iter().any(|s| s == "notify")
+    /// Like for [`Characteristic`]s, the [`Uuid`] determines the data format of the descriptor's
+    /// value.
+    pub async fn uuid(&self) -> Result<Uuid> {
+        let uuid: String = self.session.property(self.proxy.inner(), "UUID").await?;
+        uuid.parse().map_err(Error::from)
     }
 
-    /// Returns a [`bool`] indicating whether the device supports sending *indications* of changes
-    /// made to the [`Characteristic`]'s value.
+    /// Returns the [`DescriptorFlags`] associated with this [`Descriptor`].
     ///
-    /// Indications work almost exactly like notifications, but include an acknowledgement by the
-    /// GATT client (host).
-    pub fn can_indicate(&self) -> bool {
-        self.flags.iter().any(|s| s == "indicate")
+    /// These flags indicate which operations the [`Descriptor`] supports.
+    pub async fn flags(&self) -> Result<DescriptorFlags> {
+        self.session
+            .property(self.proxy.inner(), "Flags")
+            .await
+            .map(|flags| DescriptorFlags { flags })
     }
 
-    /// Returns a [`bool`] indicating whether the device allows host-initiated reads of the
-    /// [`Characteristic`]'s value.
+    /// Reads the current value of this [`Descriptor`] from the device.
+    pub async fn read(&self) -> Result<Vec<u8>> {
+        self.read_with(&ReadOptions::new()).await
+    }
+
+    /// Reads the value of this [`Descriptor`] using the given [`ReadOptions`].
     ///
-    /// Note that many [`Characteristic`]s do *not* allow host-initiated reads, but *do* support
-    /// device-initiated notifications (see [`CharacteristicFlags::can_notify`]).
+    /// Long reads use the MTU of the [`Characteristic`] this [`Descriptor`] belongs to, unless an
+    /// MTU is set via [`ReadOptions::mtu`].
+    pub async fn read_with(&self, options: &ReadOptions) -> Result<Vec<u8>> {
+        let mtu = match (options.long, options.mtu) {
+            (false, _) => None,
+            (true, Some(mtu)) => Some(mtu),
+            (true, None) => Some(self.characteristic.mtu().await.map_err(Error::from)?),
+        };
+        read_value(options, mtu, |opts| async move {
+            self.proxy.read_value(&opts).await.map_err(Error::from)
+        })
+        .await
+    }
+
+    /// Writes a new value to this [`Descriptor`].
+    pub async fn write(&self, value: &[u8]) -> Result<()> {
+        self.write_with(value, &WriteOptions::new()).await
+    }
+
+    /// Writes a new value to this [`Descriptor`] using the given [`WriteOptions`].
+    ///
+    /// Descriptors are always written with acknowledgement, so [`WriteOptions::write_type`] is
+    /// ignored.
+    pub async fn write_with(&self, value: &[u8], options: &WriteOptions) -> Result<()> {
+        let opts = private::WriteOptions {
+            offset: Some(options.offset),
+            ty: None,
+            mtu: options.mtu,
+            device: options.device.clone(),
+            link: None,
+            prepare_authorize: options.prepare_authorize.then_some(true),
+        };
+        self.proxy
+            .write_value(value, &opts)
+            .await
+            .map_err(Error::from)
+    }
+}
+
+/// A set of flags detailing the supported operations on a [`Descriptor`].
+#[derive(Debug)]
+pub struct DescriptorFlags {
+    flags: Vec<String>,
+}
+
+impl DescriptorFlags {
+    /// Returns a [`bool`] indicating whether the device allows host-initiated reads of the
+    /// [`Descriptor`]'s value.
     pub fn can_read(&self) -> bool {
         self.flags.iter().any(|s| s == "read")
     }
 
     /// Returns a [`bool`] indicating whether the device allows the host to set the
-    /// [`Characteristic`]'s value.
+    /// [`Descriptor`]'s value.
     pub fn can_write(&self) -> bool {
         self.flags.iter().any(|s| s == "write")
     }
 }
 
+/// The maximum length of an attribute value, as defined by the Bluetooth Core Specification.
+const MAX_ATTRIBUTE_LEN: usize = 512;
+
+/// Reads an attribute value via `read_chunk`, performing a long read if `options.long` is set.
+///
+/// `mtu` must be [`Some`] if a long read is requested.
+async fn read_value<F, Fut>(
+    options: &ReadOptions,
+    mtu: Option<u16>,
+    mut read_chunk: F,
+) -> Result<Vec<u8>>
+where
+    F: FnMut(private::ReadOptions) -> Fut,
+    Fut: Future<Output = Result<Vec<u8>>>,
+{
+    let opts = |offset| private::ReadOptions {
+        offset: Some(offset),
+        mtu: options.mtu,
+        device: options.device.clone(),
+    };
+
+    let Some(mtu) = mtu.filter(|_| options.long) else {
+        return read_chunk(opts(options.offset)).await;
+    };
+
+    // Every ATT Read (Blob) Response carries at most `MTU - 1` Bytes of the value. A response
+    // that is shorter than that marks the end of the attribute value.
+    let max_chunk = usize::from(mtu.saturating_sub(1)).max(1);
+
+    let mut value = Vec::new();
+    let mut offset = options.offset;
+    loop {
+        let chunk = read_chunk(opts(offset)).await?;
+        let len = chunk.len();
+        value.extend_from_slice(&chunk);
+
+        // BlueZ may already perform a long read on our behalf, in which case the response is
+        // longer than a single PDU and contains the rest of the value.
+        if len == 0 || len != max_chunk || value.len() >= MAX_ATTRIBUTE_LEN {
+            break;
+        }
+
+        offset = match u16::try_from(len)
+            .ok()
+            .and_then(|len| offset.checked_add(len))
+        {
+            Some(offset) => offset,
+            None => break,
+        };
+        log::trace!(
+            "read {} bytes, continuing long read at offset {}",
+            len,
+            offset
+        );
+    }
+
+    Ok(value)
+}
+
+/// Options for reading the value of a [`Characteristic`].
+///
+/// Passed to [`Characteristic::read_with`].
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    offset: u16,
+    mtu: Option<u16>,
+    device: Option<ObjectPath<'static>>,
+    long: bool,
+}
+
+impl ReadOptions {
+    /// Creates a new [`ReadOptions`] object with the default settings.
+    ///
+    /// By default, a single read starting at offset 0 is performed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the offset (in Bytes) into the attribute value at which to start reading.
+    pub fn offset(mut self, offset: u16) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets the MTU to assume for the read.
+    ///
+    /// If unset, the MTU reported by [`Characteristic::mtu`] is used for long reads.
+    pub fn mtu(mut self, mtu: u16) -> Self {
+        self.mtu = Some(mtu);
+        self
+    }
+
+    /// Sets the [`Device`] on whose behalf the read is performed.
+    pub fn device(mut self, device: &Device) -> Self {
+        self.device = Some(device.path());
+        self
+    }
+
+    /// Enables or disables "read long" mode.
+    ///
+    /// In this mode, [`Characteristic::read_with`] keeps reading at increasing offsets until the
+    /// whole value has been read, which allows reading values that don't fit into a single ATT
+    /// PDU.
+    pub fn read_long(mut self, long: bool) -> Self {
+        self.long = long;
+        self
+    }
+}
+
+/// The type of ATT procedure used to write a [`Characteristic`]'s value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WriteType {
+    /// Write Without Response.
+    ///
+    /// The device does not acknowledge the write, which allows for higher throughput, but gives no
+    /// indication of whether the write was successful.
+    Command,
+    /// Write With Response.
+    ///
+    /// The device acknowledges every write, and the write operation only completes once the
+    /// acknowledgement has been received.
+    Request,
+    /// Reliable Write.
+    ///
+    /// The written value is echoed back by the device and verified before the write is committed.
+    Reliable,
+}
+
+impl WriteType {
+    fn as_str(self) -> &'static str {
+        match self {
+            WriteType::Command => "command",
+            WriteType::Request => "request",
+            WriteType::Reliable => "reliable",
+        }
+    }
+}
+
+/// Options for writing the value of a [`Characteristic`].
+///
+/// Passed to [`Characteristic::write_with`].
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    ty: Option<WriteType>,
+    offset: u16,
+    mtu: Option<u16>,
+    device: Option<ObjectPath<'static>>,
+    prepare_authorize: bool,
+}
+
+impl WriteOptions {
+    /// Creates a new [`WriteOptions`] object with the default settings.
+    ///
+    /// By default, the value is written at offset 0, and BlueZ chooses the [`WriteType`] based on
+    /// the [`CharacteristicFlags`] of the [`Characteristic`] (see
+    /// [`CharacteristicFlags::default_write_type`]).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the [`WriteType`] to use.
+    pub fn write_type(mut self, ty: WriteType) -> Self {
+        self.ty = Some(ty);
+        self
+    }
+
+    /// Sets the offset (in Bytes) into the attribute value at which to start writing.
+    pub fn offset(mut self, offset: u16) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets the MTU to assume for the write.
+    pub fn mtu(mut self, mtu: u16) -> Self {
+        self.mtu = Some(mtu);
+        self
+    }
+
+    /// Sets the [`Device`] on whose behalf the write is performed.
+    pub fn device(mut self, device: &Device) -> Self {
+        self.device = Some(device.path());
+        self
+    }
+
+    /// Sets whether the write is a "prepare authorization" request.
+    ///
+    /// If `true`, the value is not actually written, and the device is only asked to authorize the
+    /// write in a Prepare Write Request.
+    pub fn prepare_authorize(mut self, prepare_authorize: bool) -> Self {
+        self.prepare_authorize = prepare_authorize;
+        self
+    }
+}
+
 /// A stream of changes to the value of a [`Characteristic`].
 ///
-/// Returned by [`Characteristic::subscribe`].
+/// Returned by [`Characteristic::subscribe`] and [`Characteristic::acquire_notify`].
 pub struct ValueStream {
-    stream: PropertyStream<'static, Vec<u8>>,
+    inner: ValueStreamKind,
+    watch: DisconnectWatch,
+}
+
+enum ValueStreamKind {
+    Property(PropertyStream<'static, Vec<u8>>, Subscription),
+    Acquired(NotifySocket),
+    Unsubscribed,
+}
+
+/// A reference to the notifications of a [`Characteristic`], enabled via `StartNotify`.
+struct Subscription {
+    session: Session,
+    proxy: GattCharacteristicProxy<'static>,
+}
+
+impl Subscription {
+    async fn start(session: &Session, proxy: &GattCharacteristicProxy<'static>) -> Result<Self> {
+        let start = async { proxy.start_notify().await.map_err(Error::from) };
+        session
+            .notify_refs
+            .acquire(proxy.path().as_str(), start, stop_notify(proxy))
+            .await?;
+
+        Ok(Self {
+            session: session.clone(),
+            proxy: proxy.clone(),
+        })
+    }
+
+    async fn stop(self) -> Result<()> {
+        let path = self.proxy.path().as_str();
+        let stop = stop_notify(&self.proxy);
+        self.session.notify_refs.release(path, stop).await
+    }
+
+    fn stop_in_background(self) {
+        let path = self.proxy.path().as_str();
+        let stop = stop_notify(&self.proxy);
+        self.session.notify_refs.release_in_background(path, stop);
+    }
+}
+
+fn stop_notify(
+    proxy: &GattCharacteristicProxy<'static>,
+) -> impl Future<Output = Result<()>> + Send + 'static {
+    let proxy = proxy.clone();
+    async move { proxy.stop_notify().await.map_err(Error::from) }
 }
 
 impl ValueStream {
@@ -293,15 +845,104 @@ impl ValueStream {
     /// caller should assume that something higher up has gone wrong that will not recover on its
     /// own. The [`ValueStream`] should be recreated.
     ///
-    /// Note that this method is not guaranteed to fail if the [`Device`] is disconnected (it can
-    /// block forever). It is recommended to use a timeout with this method (or on some containing
-    /// future).
+    /// If the [`Device`] disconnects, or the [`Characteristic`] disappears, this method returns an
+    /// error for which [`Error::is_disconnected`] returns `true`.
     ///
     /// [`Device`]: crate::device::Device
     pub async fn next(&mut self) -> Result<Vec<u8>> {
-        match self.stream.next().await {
-            Some(changed) => changed.get().await.map_err(Error::from),
-            None => Err(Error::from("notification stream ended")),
+        let value = pin!(async {
+            match &mut self.inner {
+                ValueStreamKind::Property(stream, _) => match stream.next().await {
+                    Some(changed) => changed.get().await.map_err(Error::from),
+                    None => Err(Error::from("notification stream ended")),
+                },
+                ValueStreamKind::Acquired(socket) => match socket.next().await {
+                    Some(res) => res.map_err(Error::from),
+                    // BlueZ closes the socket when the device disconnects.
+                    None => Err(Error::disconnected()),
+                },
+                ValueStreamKind::Unsubscribed => unreachable!(),
+            }
+        });
+
+        match select(value, pin!(self.watch.wait())).await {
+            Either::Left((res, _)) => res,
+            Either::Right((err, _)) => Err(err),
+        }
+    }
+
+    /// Stops receiving value changes, and disables notifications/indications on the device if no
+    /// other [`ValueStream`] for the same [`Characteristic`] remains.
+    ///
+    /// Dropping a [`ValueStream`] has the same effect, but happens in the background and any error
+    /// is only logged. This method allows waiting for completion and observing errors.
+    pub async fn unsubscribe(mut self) -> Result<()> {
+        match std::mem::replace(&mut self.inner, ValueStreamKind::Unsubscribed) {
+            ValueStreamKind::Property(_, subscription) => subscription.stop().await,
+            // Closing the socket disables notifications.
+            ValueStreamKind::Acquired(_) | ValueStreamKind::Unsubscribed => Ok(()),
+        }
+    }
+}
+
+impl Drop for ValueStream {
+    fn drop(&mut self) {
+        if let ValueStreamKind::Property(_, subscription) =
+            std::mem::replace(&mut self.inner, ValueStreamKind::Unsubscribed)
+        {
+            subscription.stop_in_background();
+        }
+    }
+}
+
+/// A handle for writing values to a [`Characteristic`] without response.
+///
+/// Returned by [`Characteristic::acquire_write`].
+pub struct ValueWriter {
+    mtu: u16,
+    inner: ValueWriterKind,
+}
+
+enum ValueWriterKind {
+    Acquired(WriteSocket),
+    Fallback(GattCharacteristicProxy<'static>),
+}
+
+impl ValueWriter {
+    /// Returns the ATT MTU of the connection in Bytes.
+    ///
+    /// Each value passed to [`ValueWriter::write`] must not be longer than `MTU - 3` Bytes.
+    pub fn mtu(&self) -> u16 {
+        self.mtu
+    }
+
+    /// Returns a [`bool`] indicating whether this [`ValueWriter`] writes through a dedicated
+    /// socket (`true`), or falls back to writing via D-Bus (`false`).
+    pub fn is_acquired(&self) -> bool {
+        matches!(self.inner, ValueWriterKind::Acquired(_))
+    }
+
+    /// Writes a new value to the [`Characteristic`].
+    ///
+    /// Since the write is not acknowledged by the device, completion of this method only means
+    /// that the value has been queued for transmission.
+    ///
+    /// # Errors
+    ///
+    /// If the socket was closed by BlueZ (for example, because the [`Device`] disconnected), this
+    /// method will return an error and the [`ValueWriter`] should be recreated.
+    ///
+    /// [`Device`]: crate::device::Device
+    pub async fn write(&mut self, value: &[u8]) -> Result<()> {
+        match &mut self.inner {
+            ValueWriterKind::Acquired(socket) => socket.write(value).await.map_err(Error::from),
+            ValueWriterKind::Fallback(proxy) => {
+                let opts = private::WriteOptions {
+                    ty: Some(WriteType::Command.as_str()),
+                    ..Default::default()
+                };
+                proxy.write_value(value, &opts).await.map_err(Error::from)
+            }
         }
     }
 }
@@ -0,0 +1,258 @@
+//! Socket-based notification and write paths (`AcquireNotify` / `AcquireWrite`).
+//!
+//! BlueZ hands out a `SOCK_SEQPACKET` socket for these, where every packet is one notification or
+//! one write. To stay independent of any particular async runtime, the sockets are serviced by
+//! dedicated threads that exchange packets with the async side through a [`Pipe`].
+
+use std::{
+    collections::VecDeque,
+    future::poll_fn,
+    io,
+    net::Shutdown,
+    os::{
+        fd::{AsRawFd, FromRawFd, IntoRawFd},
+        unix::net::UnixDatagram,
+    },
+    sync::{mpsc, Arc, Mutex},
+    task::{Poll, Waker},
+    thread,
+};
+
+use zbus::zvariant::OwnedFd;
+
+use super::MAX_ATTRIBUTE_LEN;
+
+/// An unbounded single-consumer queue whose consumer can `await` new items.
+pub(crate) struct Pipe<T> {
+    state: Mutex<PipeState<T>>,
+}
+
+struct PipeState<T> {
+    queue: VecDeque<T>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl<T> Pipe<T> {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(PipeState {
+                queue: VecDeque::new(),
+                closed: false,
+                waker: None,
+            }),
+        })
+    }
+
+    pub(crate) fn push(&self, item: T) {
+        let mut state = self.state.lock().unwrap();
+        state.queue.push_back(item);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    pub(crate) fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Waits for the next item, returning [`None`] once the [`Pipe`] is closed and drained.
+    pub(crate) async fn pop(&self) -> Option<T> {
+        poll_fn(|cx| {
+            let mut state = self.state.lock().unwrap();
+            if let Some(item) = state.queue.pop_front() {
+                Poll::Ready(Some(item))
+            } else if state.closed {
+                Poll::Ready(None)
+            } else {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+fn into_socket(fd: OwnedFd) -> UnixDatagram {
+    // Safety: `fd` is an open socket handed to us by BlueZ, and we take sole ownership of it.
+    unsafe { UnixDatagram::from_raw_fd(fd.into_raw_fd()) }
+}
+
+/// Returns whether the peer of `socket` has hung up (or `socket` has been shut down), and no more
+/// data is queued for reading.
+///
+/// Empty packets don't count as queued data, so trailing empty packets sent right before hanging up
+/// are indistinguishable from the end of the stream.
+fn is_closed(socket: &UnixDatagram) -> bool {
+    let mut pollfd = libc::pollfd {
+        fd: socket.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // Safety: `pollfd` points to a single valid `pollfd` structure.
+    let res = unsafe { libc::poll(&mut pollfd, 1, 0) };
+    if res <= 0 || pollfd.revents & libc::POLLHUP == 0 {
+        return false;
+    }
+
+    let mut queued: libc::c_int = 0;
+    // Safety: `FIONREAD` writes a single `c_int` to the given pointer.
+    let res = unsafe { libc::ioctl(socket.as_raw_fd(), libc::FIONREAD, &mut queued) };
+    res != 0 || queued == 0
+}
+
+/// The receiving end of an `AcquireNotify` socket.
+pub(crate) struct NotifySocket {
+    /// Handle used to shut down the socket, which unblocks the reader thread.
+    socket: UnixDatagram,
+    packets: Arc<Pipe<io::Result<Vec<u8>>>>,
+}
+
+impl NotifySocket {
+    pub(crate) fn new(fd: OwnedFd, mtu: u16) -> io::Result<Self> {
+        let socket = into_socket(fd);
+        let reader = socket.try_clone()?;
+        let packets = Pipe::new();
+        let pipe = packets.clone();
+        thread::Builder::new()
+            .name("blues-notify".into())
+            .spawn(move || {
+                let mut buf = vec![0; usize::from(mtu).max(MAX_ATTRIBUTE_LEN)];
+                loop {
+                    match reader.recv(&mut buf) {
+                        // A 0-byte read is either an empty notification, or means that the socket
+                        // was closed by BlueZ (eg. because the device disconnected) or shut down
+                        // by us. Packets that were sent before closing are still delivered.
+                        Ok(0) if is_closed(&reader) => break,
+                        Ok(n) => pipe.push(Ok(buf[..n].to_vec())),
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(e) => {
+                            pipe.push(Err(e));
+                            break;
+                        }
+                    }
+                }
+                pipe.close();
+            })?;
+
+        Ok(Self { socket, packets })
+    }
+
+    /// Waits for the next notification, returning [`None`] once the socket has been closed.
+    pub(crate) async fn next(&self) -> Option<io::Result<Vec<u8>>> {
+        self.packets.pop().await
+    }
+}
+
+impl Drop for NotifySocket {
+    fn drop(&mut self) {
+        self.socket.shutdown(Shutdown::Both).ok();
+    }
+}
+
+/// The sending end of an `AcquireWrite` socket.
+pub(crate) struct WriteSocket {
+    packets: mpsc::Sender<Vec<u8>>,
+    results: Arc<Pipe<io::Result<()>>>,
+    /// Number of packets whose results haven't been consumed (because a `write` was cancelled).
+    in_flight: usize,
+}
+
+impl WriteSocket {
+    pub(crate) fn new(fd: OwnedFd) -> io::Result<Self> {
+        let socket = into_socket(fd);
+        let (packets, rx) = mpsc::channel::<Vec<u8>>();
+        let results = Pipe::new();
+        let pipe = results.clone();
+        thread::Builder::new()
+            .name("blues-write".into())
+            .spawn(move || {
+                // Ends when the `WriteSocket` (and thus the `Sender`) is dropped.
+                for packet in rx {
+                    pipe.push(socket.send(&packet).map(drop));
+                }
+                pipe.close();
+            })?;
+
+        Ok(Self {
+            packets,
+            results,
+            in_flight: 0,
+        })
+    }
+
+    /// Sends `value` as a single packet, and waits until it has been handed to the kernel.
+    pub(crate) async fn write(&mut self, value: &[u8]) -> io::Result<()> {
+        let closed = || io::Error::new(io::ErrorKind::BrokenPipe, "write socket closed");
+
+        // Discard the results of writes whose futures were dropped before completion.
+        while self.in_flight > 0 {
+            self.results.pop().await;
+            self.in_flight -= 1;
+        }
+
+        self.packets.send(value.to_vec()).map_err(|_| closed())?;
+        self.in_flight += 1;
+        let res = self.results.pop().await.unwrap_or_else(|| Err(closed()));
+        self.in_flight -= 1;
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a connected `SOCK_SEQPACKET` pair, like the ones BlueZ hands out.
+    fn seqpacket_pair() -> (OwnedFd, UnixDatagram) {
+        let mut fds = [0; 2];
+        // Safety: `fds` has room for the two file descriptors.
+        let res = unsafe {
+            libc::socketpair(
+                libc::AF_UNIX,
+                libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC,
+                0,
+                fds.as_mut_ptr(),
+            )
+        };
+        assert_eq!(res, 0, "{}", io::Error::last_os_error());
+        // Safety: both file descriptors were just created and are owned by nothing else.
+        unsafe {
+            (
+                OwnedFd::from_raw_fd(fds[0]),
+                UnixDatagram::from_raw_fd(fds[1]),
+            )
+        }
+    }
+
+    #[test]
+    fn notify_socket_empty_packets() {
+        let (fd, peer) = seqpacket_pair();
+        let socket = NotifySocket::new(fd, 23).unwrap();
+
+        peer.send(&[]).unwrap();
+        peer.send(&[1, 2]).unwrap();
+        assert_eq!(pollster::block_on(socket.next()).unwrap().unwrap(), []);
+        assert_eq!(pollster::block_on(socket.next()).unwrap().unwrap(), [1, 2]);
+
+        drop(peer);
+        assert!(pollster::block_on(socket.next()).is_none());
+    }
+
+    #[test]
+    fn notify_socket_drains_after_hangup() {
+        let (fd, peer) = seqpacket_pair();
+        peer.send(&[]).unwrap();
+        peer.send(&[3]).unwrap();
+        drop(peer);
+
+        let socket = NotifySocket::new(fd, 23).unwrap();
+        assert_eq!(pollster::block_on(socket.next()).unwrap().unwrap(), []);
+        assert_eq!(pollster::block_on(socket.next()).unwrap().unwrap(), [3]);
+        assert!(pollster::block_on(socket.next()).is_none());
+    }
+}
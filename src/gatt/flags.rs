@@ -0,0 +1,397 @@
+//! Typed [`Characteristic`] flags.
+//!
+//! [`Characteristic`]: super::Characteristic
+
+use std::fmt;
+
+use super::WriteType;
+
+/// A single flag describing an operation or requirement of a [`Characteristic`].
+///
+/// A [`Characteristic`]'s flags can be queried via [`Characteristic::flags`], which returns a
+/// [`CharacteristicFlags`] set.
+///
+/// [`Characteristic`]: super::Characteristic
+/// [`Characteristic::flags`]: super::Characteristic::flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CharacteristicFlag {
+    /// The value can be broadcast in advertisements.
+    Broadcast,
+    /// The value can be read by the host.
+    Read,
+    /// The value can be written by the host without acknowledgement.
+    WriteWithoutResponse,
+    /// The value can be written by the host, with acknowledgement.
+    Write,
+    /// The device can notify the host of changes to the value.
+    Notify,
+    /// The device can indicate changes to the value (notifications with acknowledgement).
+    Indicate,
+    /// The value can be written with an authentication signature.
+    AuthenticatedSignedWrites,
+    /// Additional properties are defined in the Characteristic Extended Properties descriptor.
+    ExtendedProperties,
+    /// The value supports reliable writes.
+    ReliableWrite,
+    /// The Characteristic User Description descriptor can be written.
+    WritableAuxiliaries,
+    /// Reading the value requires an encrypted link.
+    EncryptRead,
+    /// Writing the value requires an encrypted link.
+    EncryptWrite,
+    /// Notifications require an encrypted link.
+    EncryptNotify,
+    /// Indications require an encrypted link.
+    EncryptIndicate,
+    /// Reading the value requires an encrypted and authenticated link.
+    EncryptAuthenticatedRead,
+    /// Writing the value requires an encrypted and authenticated link.
+    EncryptAuthenticatedWrite,
+    /// Notifications require an encrypted and authenticated link.
+    EncryptAuthenticatedNotify,
+    /// Indications require an encrypted and authenticated link.
+    EncryptAuthenticatedIndicate,
+    /// Reading the value requires a Secure Connections link.
+    SecureRead,
+    /// Writing the value requires a Secure Connections link.
+    SecureWrite,
+    /// Notifications require a Secure Connections link.
+    SecureNotify,
+    /// Indications require a Secure Connections link.
+    SecureIndicate,
+    /// Accessing the value requires authorization by the device.
+    Authorize,
+}
+
+impl CharacteristicFlag {
+    const ALL: &'static [Self] = &[
+        Self::Broadcast,
+        Self::Read,
+        Self::WriteWithoutResponse,
+        Self::Write,
+        Self::Notify,
+        Self::Indicate,
+        Self::AuthenticatedSignedWrites,
+        Self::ExtendedProperties,
+        Self::ReliableWrite,
+        Self::WritableAuxiliaries,
+        Self::EncryptRead,
+        Self::EncryptWrite,
+        Self::EncryptNotify,
+        Self::EncryptIndicate,
+        Self::EncryptAuthenticatedRead,
+        Self::EncryptAuthenticatedWrite,
+        Self::EncryptAuthenticatedNotify,
+        Self::EncryptAuthenticatedIndicate,
+        Self::SecureRead,
+        Self::SecureWrite,
+        Self::SecureNotify,
+        Self::SecureIndicate,
+        Self::Authorize,
+    ];
+
+    /// Returns the name BlueZ uses for this flag (eg. `write-without-response`).
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Broadcast => "broadcast",
+            Self::Read => "read",
+            Self::WriteWithoutResponse => "write-without-response",
+            Self::Write => "write",
+            Self::Notify => "notify",
+            Self::Indicate => "indicate",
+            Self::AuthenticatedSignedWrites => "authenticated-signed-writes",
+            Self::ExtendedProperties => "extended-properties",
+            Self::ReliableWrite => "reliable-write",
+            Self::WritableAuxiliaries => "writable-auxiliaries",
+            Self::EncryptRead => "encrypt-read",
+            Self::EncryptWrite => "encrypt-write",
+            Self::EncryptNotify => "encrypt-notify",
+            Self::EncryptIndicate => "encrypt-indicate",
+            Self::EncryptAuthenticatedRead => "encrypt-authenticated-read",
+            Self::EncryptAuthenticatedWrite => "encrypt-authenticated-write",
+            Self::EncryptAuthenticatedNotify => "encrypt-authenticated-notify",
+            Self::EncryptAuthenticatedIndicate => "encrypt-authenticated-indicate",
+            Self::SecureRead => "secure-read",
+            Self::SecureWrite => "secure-write",
+            Self::SecureNotify => "secure-notify",
+            Self::SecureIndicate => "secure-indicate",
+            Self::Authorize => "authorize",
+        }
+    }
+
+    fn from_name(s: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|flag| flag.name() == s)
+    }
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+impl fmt::Display for CharacteristicFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The link security required to perform an operation on a [`Characteristic`].
+///
+/// Returned by [`CharacteristicFlags::read_security`] and [`CharacteristicFlags::write_security`].
+///
+/// [`Characteristic`]: super::Characteristic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum SecurityLevel {
+    /// No security requirements.
+    Open,
+    /// The link has to be encrypted.
+    Encrypted,
+    /// The link has to be encrypted and authenticated (requires pairing with MITM protection).
+    Authenticated,
+    /// The link has to use LE Secure Connections.
+    SecureConnections,
+}
+
+/// A set of flags detailing the supported operations on a [`Characteristic`].
+///
+/// Flags that are not known to this library are preserved and can be accessed via
+/// [`CharacteristicFlags::unknown`].
+///
+/// [`Characteristic`]: super::Characteristic
+#[derive(Clone, PartialEq, Eq)]
+pub struct CharacteristicFlags {
+    bits: u32,
+    unknown: Vec<String>,
+}
+
+impl CharacteristicFlags {
+    pub(crate) fn from_strings(flags: Vec<String>) -> Self {
+        let mut bits = 0;
+        let mut unknown = Vec::new();
+        for flag in flags {
+            match CharacteristicFlag::from_name(&flag) {
+                Some(flag) => bits |= flag.bit(),
+                None => unknown.push(flag),
+            }
+        }
+        Self { bits, unknown }
+    }
+
+    /// Returns a [`bool`] indicating whether the given [`CharacteristicFlag`] is set.
+    pub fn contains(&self, flag: CharacteristicFlag) -> bool {
+        self.bits & flag.bit() != 0
+    }
+
+    /// Returns an iterator over all known [`CharacteristicFlag`]s in this set.
+    pub fn iter(&self) -> impl Iterator<Item = CharacteristicFlag> + '_ {
+        CharacteristicFlag::ALL
+            .iter()
+            .copied()
+            .filter(|flag| self.contains(*flag))
+    }
+
+    /// Returns an iterator over the names of all flags reported by BlueZ that are not known to
+    /// this library.
+    pub fn unknown(&self) -> impl Iterator<Item = &str> + '_ {
+        self.unknown.iter().map(|s| &**s)
+    }
+
+    /// Returns a [`bool`] indicating whether the device can notify the host of changes made to the
+    /// [`Characteristic`]'s value.
+    ///
+    /// If this returns `true`, [`Characteristic::subscribe`] can be used to obtain a
+    /// [`ValueStream`] that reports every notification.
+    ///
+    /// [`Characteristic`]: super::Characteristic
+    /// [`Characteristic::subscribe`]: super::Characteristic::subscribe
+    /// [`ValueStream`]: super::ValueStream
+    pub fn can_notify(&self) -> bool {
+        self.contains(CharacteristicFlag::Notify)
+    }
+
+    /// Returns a [`bool`] indicating whether the device supports sending *indications* of changes
+    /// made to the [`Characteristic`]'s value.
+    ///
+    /// Indications work almost exactly like notifications, but include an acknowledgement by the
+    /// GATT client (host).
+    ///
+    /// [`Characteristic`]: super::Characteristic
+    pub fn can_indicate(&self) -> bool {
+        self.contains(CharacteristicFlag::Indicate)
+    }
+
+    /// Returns a [`bool`] indicating whether the device allows host-initiated reads of the
+    /// [`Characteristic`]'s value.
+    ///
+    /// Note that many [`Characteristic`]s do *not* allow host-initiated reads, but *do* support
+    /// device-initiated notifications (see [`CharacteristicFlags::can_notify`]).
+    ///
+    /// [`Characteristic`]: super::Characteristic
+    pub fn can_read(&self) -> bool {
+        self.contains(CharacteristicFlag::Read)
+    }
+
+    /// Returns a [`bool`] indicating whether the device allows the host to set the
+    /// [`Characteristic`]'s value.
+    ///
+    /// [`Characteristic`]: super::Characteristic
+    pub fn can_write(&self) -> bool {
+        self.contains(CharacteristicFlag::Write)
+    }
+
+    /// Returns a [`bool`] indicating whether the device allows the host to set the
+    /// [`Characteristic`]'s value without acknowledging the write.
+    ///
+    /// [`Characteristic`]: super::Characteristic
+    pub fn can_write_without_response(&self) -> bool {
+        self.contains(CharacteristicFlag::WriteWithoutResponse)
+    }
+
+    /// Returns a [`bool`] indicating whether the device supports reliable writes to the
+    /// [`Characteristic`]'s value.
+    ///
+    /// [`Characteristic`]: super::Characteristic
+    pub fn can_reliable_write(&self) -> bool {
+        self.contains(CharacteristicFlag::ReliableWrite)
+    }
+
+    /// Returns the [`WriteType`] that BlueZ uses to write to the [`Characteristic`] when none is
+    /// explicitly requested.
+    ///
+    /// Like BlueZ, this prefers reliable writes, then acknowledged writes, then unacknowledged ones.
+    /// Returns [`None`] if the [`Characteristic`] is not writable.
+    ///
+    /// [`Characteristic`]: super::Characteristic
+    pub fn default_write_type(&self) -> Option<WriteType> {
+        if self.can_reliable_write() {
+            Some(WriteType::Reliable)
+        } else if self.can_write() {
+            Some(WriteType::Request)
+        } else if self.can_write_without_response() {
+            Some(WriteType::Command)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the [`SecurityLevel`] required to read the [`Characteristic`]'s value.
+    ///
+    /// [`Characteristic`]: super::Characteristic
+    pub fn read_security(&self) -> SecurityLevel {
+        self.security(
+            CharacteristicFlag::SecureRead,
+            CharacteristicFlag::EncryptAuthenticatedRead,
+            CharacteristicFlag::EncryptRead,
+        )
+    }
+
+    /// Returns the [`SecurityLevel`] required to write the [`Characteristic`]'s value.
+    ///
+    /// [`Characteristic`]: super::Characteristic
+    pub fn write_security(&self) -> SecurityLevel {
+        self.security(
+            CharacteristicFlag::SecureWrite,
+            CharacteristicFlag::EncryptAuthenticatedWrite,
+            CharacteristicFlag::EncryptWrite,
+        )
+    }
+
+    /// Returns the [`SecurityLevel`] required to receive notifications or indications.
+    pub fn notify_security(&self) -> SecurityLevel {
+        self.security(
+            CharacteristicFlag::SecureNotify,
+            CharacteristicFlag::EncryptAuthenticatedNotify,
+            CharacteristicFlag::EncryptNotify,
+        )
+        .max(self.security(
+            CharacteristicFlag::SecureIndicate,
+            CharacteristicFlag::EncryptAuthenticatedIndicate,
+            CharacteristicFlag::EncryptIndicate,
+        ))
+    }
+
+    /// Returns a [`bool`] indicating whether accessing the [`Characteristic`] requires
+    /// authorization.
+    ///
+    /// [`Characteristic`]: super::Characteristic
+    pub fn requires_authorization(&self) -> bool {
+        self.contains(CharacteristicFlag::Authorize)
+    }
+
+    fn security(
+        &self,
+        secure: CharacteristicFlag,
+        authenticated: CharacteristicFlag,
+        encrypted: CharacteristicFlag,
+    ) -> SecurityLevel {
+        if self.contains(secure) {
+            SecurityLevel::SecureConnections
+        } else if self.contains(authenticated) {
+            SecurityLevel::Authenticated
+        } else if self.contains(encrypted) {
+            SecurityLevel::Encrypted
+        } else {
+            SecurityLevel::Open
+        }
+    }
+}
+
+impl fmt::Debug for CharacteristicFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut set = f.debug_set();
+        for flag in self.iter() {
+            set.entry(&format_args!("{}", flag));
+        }
+        set.entries(self.unknown()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(names: &[&str]) -> CharacteristicFlags {
+        CharacteristicFlags::from_strings(names.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn parse() {
+        let flags = flags(&["read", "write-without-response", "encrypt-read", "x-vendor"]);
+        assert!(flags.can_read());
+        assert!(!flags.can_write());
+        assert!(flags.can_write_without_response());
+        assert_eq!(
+            flags.iter().collect::<Vec<_>>(),
+            [
+                CharacteristicFlag::Read,
+                CharacteristicFlag::WriteWithoutResponse,
+                CharacteristicFlag::EncryptRead,
+            ]
+        );
+        assert_eq!(flags.unknown().collect::<Vec<_>>(), ["x-vendor"]);
+        assert_eq!(flags.default_write_type(), Some(WriteType::Command));
+        assert_eq!(
+            self::flags(&["write", "write-without-response"]).default_write_type(),
+            Some(WriteType::Request)
+        );
+        assert_eq!(
+            self::flags(&["write", "reliable-write"]).default_write_type(),
+            Some(WriteType::Reliable)
+        );
+        assert_eq!(flags.read_security(), SecurityLevel::Encrypted);
+        assert_eq!(flags.write_security(), SecurityLevel::Open);
+        assert_eq!(
+            format!("{:?}", flags),
+            r#"{read, write-without-response, encrypt-read, "x-vendor"}"#
+        );
+    }
+
+    #[test]
+    fn names_roundtrip() {
+        for flag in CharacteristicFlag::ALL {
+            assert_eq!(CharacteristicFlag::from_name(flag.name()), Some(*flag));
+        }
+        assert!(CharacteristicFlag::ALL.len() <= 32);
+    }
+}
@@ -0,0 +1,372 @@
+//! Decoding of the Characteristic Presentation Format descriptor.
+
+use crate::{uuid::Uuid, Error, Result};
+
+/// The decoded value of a Characteristic Presentation Format [`Descriptor`].
+///
+/// This descriptor describes how the value of the [`Characteristic`] it belongs to is formatted,
+/// and which unit it is expressed in. It can be obtained via
+/// [`Characteristic::presentation_format`].
+///
+/// [`Descriptor`]: super::Descriptor
+/// [`Characteristic`]: super::Characteristic
+/// [`Characteristic::presentation_format`]: super::Characteristic::presentation_format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresentationFormat {
+    format: Format,
+    exponent: i8,
+    unit: u16,
+    namespace: u8,
+    description: u16,
+}
+
+impl PresentationFormat {
+    /// The [`Uuid`] of the Characteristic Presentation Format descriptor.
+    pub const UUID: Uuid = Uuid::from_u16(0x2904);
+
+    /// Decodes a [`PresentationFormat`] from the raw value of the descriptor.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` does not have the expected length of 7 Bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let &[format, exponent, unit_lo, unit_hi, namespace, desc_lo, desc_hi] = bytes else {
+            return Err(Error::from(format!(
+                "invalid presentation format length (expected 7 bytes, got {})",
+                bytes.len()
+            )));
+        };
+
+        Ok(Self {
+            format: Format::from_u8(format),
+            exponent: exponent as i8,
+            unit: u16::from_le_bytes([unit_lo, unit_hi]),
+            namespace,
+            description: u16::from_le_bytes([desc_lo, desc_hi]),
+        })
+    }
+
+    /// Returns the [`Format`] of the [`Characteristic`]'s value.
+    ///
+    /// [`Characteristic`]: super::Characteristic
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns the base-10 exponent that is applied to the value.
+    ///
+    /// The actual value is `raw_value * 10^exponent`.
+    pub fn exponent(&self) -> i8 {
+        self.exponent
+    }
+
+    /// Returns the [`Uuid`] of the unit the value is expressed in.
+    ///
+    /// Units are assigned by the Bluetooth SIG in the "Assigned Numbers" document (for example,
+    /// `0x272F` is degrees Celsius).
+    pub fn unit(&self) -> Uuid {
+        Uuid::from_u16(self.unit)
+    }
+
+    /// Returns the namespace that the [`PresentationFormat::description`] belongs to.
+    ///
+    /// A value of `0x01` indicates the Bluetooth SIG namespace.
+    pub fn namespace(&self) -> u8 {
+        self.namespace
+    }
+
+    /// Returns the description of the value, as defined by the
+    /// [`PresentationFormat::namespace`].
+    pub fn description(&self) -> u16 {
+        self.description
+    }
+
+    /// Decodes a raw [`Characteristic`] value (as returned by [`Characteristic::read`] or
+    /// [`ValueStream::next`]) according to this [`PresentationFormat`].
+    ///
+    /// Returns [`None`] if the [`Format`] is not numeric, or if `value` is too short.
+    ///
+    /// [`Characteristic`]: super::Characteristic
+    /// [`Characteristic::read`]: super::Characteristic::read
+    /// [`ValueStream::next`]: super::ValueStream::next
+    pub fn decode(&self, value: &[u8]) -> Option<ScaledValue> {
+        let raw = match self.format {
+            Format::Boolean => f64::from(*value.first()? & 0b1),
+            Format::UInt2 => f64::from(*value.first()? & 0b11),
+            Format::UInt4 => f64::from(*value.first()? & 0b1111),
+            Format::UInt8 => read_uint(value, 1, 8)? as f64,
+            Format::UInt12 => read_uint(value, 2, 12)? as f64,
+            Format::UInt16 => read_uint(value, 2, 16)? as f64,
+            Format::UInt24 => read_uint(value, 3, 24)? as f64,
+            Format::UInt32 => read_uint(value, 4, 32)? as f64,
+            Format::UInt48 => read_uint(value, 6, 48)? as f64,
+            Format::UInt64 => read_uint(value, 8, 64)? as f64,
+            Format::UInt128 => read_uint(value, 16, 128)? as f64,
+            Format::SInt8 => read_sint(value, 1, 8)? as f64,
+            Format::SInt12 => read_sint(value, 2, 12)? as f64,
+            Format::SInt16 => read_sint(value, 2, 16)? as f64,
+            Format::SInt24 => read_sint(value, 3, 24)? as f64,
+            Format::SInt32 => read_sint(value, 4, 32)? as f64,
+            Format::SInt48 => read_sint(value, 6, 48)? as f64,
+            Format::SInt64 => read_sint(value, 8, 64)? as f64,
+            Format::SInt128 => read_sint(value, 16, 128)? as f64,
+            Format::Float32 => f64::from(f32::from_le_bytes(value.get(..4)?.try_into().ok()?)),
+            Format::Float64 => f64::from_le_bytes(value.get(..8)?.try_into().ok()?),
+            Format::SFloat => {
+                let raw = read_uint(value, 2, 16)? as u16;
+                decode_medfloat(
+                    i32::from((raw as i16) >> 12),
+                    read_sint(value, 2, 12)? as i32,
+                    0x07FF,
+                )
+            }
+            Format::Float => {
+                let raw = read_uint(value, 4, 32)? as u32;
+                decode_medfloat(
+                    i32::from((raw >> 24) as i8),
+                    read_sint(value, 3, 24)? as i32,
+                    0x007F_FFFF,
+                )
+            }
+            Format::DUInt16 | Format::Utf8 | Format::Utf16 | Format::Struct | Format::Other(_) => {
+                return None
+            }
+        };
+
+        Some(ScaledValue {
+            value: raw * 10f64.powi(self.exponent.into()),
+            unit: self.unit(),
+        })
+    }
+}
+
+/// The data type of a [`Characteristic`]'s value, as described by a [`PresentationFormat`].
+///
+/// [`Characteristic`]: super::Characteristic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Format {
+    /// Unsigned 1-bit value (`0` is false, `1` is true).
+    Boolean,
+    /// Unsigned 2-bit integer.
+    UInt2,
+    /// Unsigned 4-bit integer.
+    UInt4,
+    /// Unsigned 8-bit integer.
+    UInt8,
+    /// Unsigned 12-bit integer.
+    UInt12,
+    /// Unsigned 16-bit integer.
+    UInt16,
+    /// Unsigned 24-bit integer.
+    UInt24,
+    /// Unsigned 32-bit integer.
+    UInt32,
+    /// Unsigned 48-bit integer.
+    UInt48,
+    /// Unsigned 64-bit integer.
+    UInt64,
+    /// Unsigned 128-bit integer.
+    UInt128,
+    /// Signed 8-bit integer.
+    SInt8,
+    /// Signed 12-bit integer.
+    SInt12,
+    /// Signed 16-bit integer.
+    SInt16,
+    /// Signed 24-bit integer.
+    SInt24,
+    /// Signed 32-bit integer.
+    SInt32,
+    /// Signed 48-bit integer.
+    SInt48,
+    /// Signed 64-bit integer.
+    SInt64,
+    /// Signed 128-bit integer.
+    SInt128,
+    /// IEEE-754 32-bit floating point number.
+    Float32,
+    /// IEEE-754 64-bit floating point number.
+    Float64,
+    /// IEEE-11073 16-bit SFLOAT.
+    SFloat,
+    /// IEEE-11073 32-bit FLOAT.
+    Float,
+    /// IEEE-20601 format (two unsigned 16-bit integers).
+    DUInt16,
+    /// UTF-8 string.
+    Utf8,
+    /// UTF-16 string.
+    Utf16,
+    /// Opaque structure.
+    Struct,
+    /// A reserved or unknown format identifier.
+    Other(u8),
+}
+
+impl Format {
+    fn from_u8(raw: u8) -> Self {
+        match raw {
+            0x01 => Self::Boolean,
+            0x02 => Self::UInt2,
+            0x03 => Self::UInt4,
+            0x04 => Self::UInt8,
+            0x05 => Self::UInt12,
+            0x06 => Self::UInt16,
+            0x07 => Self::UInt24,
+            0x08 => Self::UInt32,
+            0x09 => Self::UInt48,
+            0x0A => Self::UInt64,
+            0x0B => Self::UInt128,
+            0x0C => Self::SInt8,
+            0x0D => Self::SInt12,
+            0x0E => Self::SInt16,
+            0x0F => Self::SInt24,
+            0x10 => Self::SInt32,
+            0x11 => Self::SInt48,
+            0x12 => Self::SInt64,
+            0x13 => Self::SInt128,
+            0x14 => Self::Float32,
+            0x15 => Self::Float64,
+            0x16 => Self::SFloat,
+            0x17 => Self::Float,
+            0x18 => Self::DUInt16,
+            0x19 => Self::Utf8,
+            0x1A => Self::Utf16,
+            0x1B => Self::Struct,
+            _ => Self::Other(raw),
+        }
+    }
+}
+
+/// A numeric [`Characteristic`] value, scaled by the exponent of its [`PresentationFormat`] and
+/// annotated with its unit.
+///
+/// Returned by [`PresentationFormat::decode`].
+///
+/// [`Characteristic`]: super::Characteristic
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaledValue {
+    value: f64,
+    unit: Uuid,
+}
+
+impl ScaledValue {
+    /// Returns the scaled numeric value.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Returns the [`Uuid`] of the unit the value is expressed in.
+    pub fn unit(&self) -> Uuid {
+        self.unit
+    }
+}
+
+/// Reads a little-endian unsigned integer with `bits` significant bits from the first `len` Bytes
+/// of `value`.
+fn read_uint(value: &[u8], len: usize, bits: u32) -> Option<u128> {
+    let mut buf = [0; 16];
+    buf[..len].copy_from_slice(value.get(..len)?);
+    let raw = u128::from_le_bytes(buf);
+    Some(if bits == 128 {
+        raw
+    } else {
+        raw & ((1 << bits) - 1)
+    })
+}
+
+/// Like [`read_uint`], but sign-extends the result.
+fn read_sint(value: &[u8], len: usize, bits: u32) -> Option<i128> {
+    let shift = 128 - bits;
+    Some(((read_uint(value, len, bits)? << shift) as i128) >> shift)
+}
+
+/// Decodes an IEEE-11073 SFLOAT or FLOAT, given its exponent and mantissa.
+///
+/// `nan` is the mantissa value denoting NaN; the other special values are derived from it.
+fn decode_medfloat(exponent: i32, mantissa: i32, nan: i32) -> f64 {
+    // Special values are only defined for an exponent of 0.
+    if exponent == 0 {
+        if mantissa == nan {
+            return f64::NAN;
+        } else if mantissa == nan - 1 {
+            return f64::INFINITY;
+        } else if mantissa == -(nan + 1) {
+            // NRes (not at this resolution)
+            return f64::NAN;
+        } else if mantissa == -(nan - 1) {
+            return f64::NEG_INFINITY;
+        } else if mantissa == -nan {
+            // Reserved for future use.
+            return f64::NAN;
+        }
+    }
+
+    f64::from(mantissa) * 10f64.powi(exponent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        // Temperature in 0.01 °C, as used by the Environmental Sensing Service.
+        let fmt =
+            PresentationFormat::from_bytes(&[0x0E, 0xFE, 0x2F, 0x27, 0x01, 0x00, 0x00]).unwrap();
+        assert_eq!(fmt.format(), Format::SInt16);
+        assert_eq!(fmt.exponent(), -2);
+        assert_eq!(fmt.unit(), Uuid::from_u16(0x272F));
+        assert_eq!(fmt.namespace(), 0x01);
+        assert_eq!(fmt.description(), 0);
+
+        let v = fmt.decode(&(-1234i16).to_le_bytes()).unwrap();
+        assert!((v.value() - -12.34).abs() < 1e-9);
+        assert_eq!(v.unit(), Uuid::from_u16(0x272F));
+
+        PresentationFormat::from_bytes(&[0x0E, 0xFE]).unwrap_err();
+        PresentationFormat::from_bytes(&[0; 8]).unwrap_err();
+    }
+
+    #[test]
+    fn decode_ints() {
+        let fmt = |format: u8| PresentationFormat::from_bytes(&[format, 0, 0, 0, 0, 0, 0]).unwrap();
+
+        assert_eq!(fmt(0x04).decode(&[0xFF]).unwrap().value(), 255.0);
+        assert_eq!(fmt(0x05).decode(&[0xFF, 0xFF]).unwrap().value(), 4095.0);
+        assert_eq!(fmt(0x0D).decode(&[0xFF, 0x0F]).unwrap().value(), -1.0);
+        assert_eq!(
+            fmt(0x07).decode(&[0x01, 0x02, 0x03]).unwrap().value(),
+            197121.0
+        );
+        assert_eq!(fmt(0x0F).decode(&[0xFE, 0xFF, 0xFF]).unwrap().value(), -2.0);
+        assert!(fmt(0x06).decode(&[0x01]).is_none());
+        assert!(fmt(0x19).decode(b"abc").is_none());
+    }
+
+    #[test]
+    fn decode_medfloats() {
+        let sfloat = PresentationFormat::from_bytes(&[0x16, 0, 0, 0, 0, 0, 0]).unwrap();
+        // mantissa 114, exponent -1
+        assert!((sfloat.decode(&[0x72, 0xF0]).unwrap().value() - 11.4).abs() < 1e-9);
+        assert!(sfloat.decode(&[0xFF, 0x07]).unwrap().value().is_nan());
+        assert_eq!(sfloat.decode(&[0xFE, 0x07]).unwrap().value(), f64::INFINITY);
+        assert_eq!(
+            sfloat.decode(&[0x02, 0x08]).unwrap().value(),
+            f64::NEG_INFINITY
+        );
+
+        let float = PresentationFormat::from_bytes(&[0x17, 0, 0, 0, 0, 0, 0]).unwrap();
+        // mantissa -5, exponent 2
+        assert_eq!(
+            float.decode(&[0xFB, 0xFF, 0xFF, 0x02]).unwrap().value(),
+            -500.0
+        );
+        assert!(float
+            .decode(&[0xFF, 0xFF, 0x7F, 0x00])
+            .unwrap()
+            .value()
+            .is_nan());
+    }
+}
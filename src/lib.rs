@@ -2,15 +2,37 @@
 
 mod adapter;
 pub mod address;
+pub mod agent;
+pub mod blocking;
+mod cache;
 pub mod device;
 mod error;
+mod executor;
 pub mod gatt;
+mod refcount;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod uuid;
 
-pub use adapter::{Adapter, DeviceStream};
-pub use error::{Error, Result};
+pub use adapter::{
+    Adapter, AdapterChange, AdapterChanges, AdapterEvent, AdapterStream, DeviceSet,
+    DeviceSetChange, DeviceStream, DiscoveryFilter, DiscoveryGuard, PowerState, Transport,
+};
+pub use error::{Error, ErrorKind, Result};
 
-use zbus::{fdo::ObjectManagerProxy, Connection};
+use std::{os::unix::net::UnixStream, sync::Arc};
+
+use cache::ObjectCache;
+use refcount::RefCounts;
+use zbus::{
+    fdo::{ObjectManager, ObjectManagerProxy},
+    names::BusName,
+    zvariant::{ObjectPath, OwnedValue},
+    Connection, ConnectionBuilder, ProxyBuilder,
+};
+
+/// The well-known D-Bus name of the BlueZ daemon.
+const BLUEZ_SERVICE: &str = "org.bluez";
 
 /// A cloneable handle to a D-Bus connection.
 ///
@@ -18,25 +40,179 @@ use zbus::{fdo::ObjectManagerProxy, Connection};
 #[derive(Clone)]
 pub struct Session {
     conn: Connection,
+    /// The D-Bus name BlueZ is reachable under (normally `org.bluez`).
+    service: BusName<'static>,
+    /// Mirror of the BlueZ object tree, used for lookups and property reads.
+    cache: Arc<ObjectCache>,
+    /// `StartNotify` references per characteristic.
+    notify_refs: Arc<RefCounts>,
+    /// `StartDiscovery` references per adapter.
+    discovery_refs: Arc<RefCounts>,
 }
 
 impl Session {
     /// Creates a new D-Bus connection.
+    ///
+    /// This connects to BlueZ on the system bus. Use [`Session::builder`] to connect to a different
+    /// bus or BlueZ instance.
     pub async fn new() -> Result<Self> {
-        Ok(Self {
-            conn: Connection::system().await.map_err(Error::from)?,
-        })
+        Self::builder().build().await
     }
 
-    /// Connects to the BlueZ D-Bus object manager.
-    async fn object_manager(&self) -> Result<ObjectManagerProxy<'static>> {
-        Ok(ObjectManagerProxy::builder(&self.conn)
-            .destination("org.bluez")
+    /// Returns a [`SessionBuilder`] for configuring which bus and BlueZ instance to connect to.
+    pub fn builder() -> SessionBuilder {
+        SessionBuilder::new()
+    }
+
+    /// Finishes `builder`, creating a proxy for the BlueZ object at `path`.
+    async fn proxy<P, T>(&self, builder: ProxyBuilder<'static, P>, path: T) -> Result<P>
+    where
+        P: From<zbus::Proxy<'static>>,
+        T: TryInto<ObjectPath<'static>>,
+        T::Error: Into<zbus::Error>,
+    {
+        builder
+            .destination(self.service.clone())
             .map_err(Error::from)?
-            .path("/")
+            .path(path)
             .map_err(Error::from)?
             .build()
             .await
-            .map_err(Error::from)?)
+            .map_err(Error::from)
+    }
+
+    /// Reads a property of the object behind `proxy`.
+    ///
+    /// The value is taken from the cached object tree if possible, and queried via `proxy`
+    /// otherwise.
+    async fn property<T>(&self, proxy: &zbus::Proxy<'_>, name: &str) -> Result<T>
+    where
+        T: TryFrom<OwnedValue>,
+        T::Error: Into<zbus::Error>,
+    {
+        let cached = self
+            .cache
+            .property(proxy.path(), proxy.interface(), name)
+            .await?;
+        match cached {
+            Some(value) => T::try_from(value).map_err(|e| Error::from(e.into())),
+            None => proxy.get_property(name).await.map_err(Error::from),
+        }
     }
+
+    /// Connects to the BlueZ D-Bus object manager.
+    async fn object_manager(&self) -> Result<ObjectManagerProxy<'static>> {
+        self.proxy(ObjectManagerProxy::builder(&self.conn), "/")
+            .await
+    }
+}
+
+/// Configures and creates a [`Session`].
+///
+/// Returned by [`Session::builder`]. By default, the [`Session`] will connect to BlueZ on the
+/// system bus.
+pub struct SessionBuilder {
+    bus: Bus,
+    service: String,
+}
+
+enum Bus {
+    System,
+    Session,
+    Address(String),
+    Stream(UnixStream),
+    /// A direct connection to the BlueZ (mock) process, without a D-Bus daemon in between.
+    #[cfg(feature = "testing")]
+    Peer(UnixStream),
+}
+
+impl SessionBuilder {
+    fn new() -> Self {
+        Self {
+            bus: Bus::System,
+            service: BLUEZ_SERVICE.to_string(),
+        }
+    }
+
+    /// Connects to the system bus (the default).
+    pub fn system_bus(mut self) -> Self {
+        self.bus = Bus::System;
+        self
+    }
+
+    /// Connects to the session bus of the current user.
+    pub fn session_bus(mut self) -> Self {
+        self.bus = Bus::Session;
+        self
+    }
+
+    /// Connects to the D-Bus daemon at the given address (eg. `unix:path=/run/dbus/socket`).
+    pub fn address(mut self, address: impl Into<String>) -> Self {
+        self.bus = Bus::Address(address.into());
+        self
+    }
+
+    /// Uses an already established connection to a D-Bus daemon.
+    ///
+    /// When the `tokio` feature is enabled, [`SessionBuilder::build`] has to be called from within
+    /// a tokio runtime.
+    pub fn unix_stream(mut self, stream: UnixStream) -> Self {
+        self.bus = Bus::Stream(stream);
+        self
+    }
+
+    /// Talks to the other end of `stream` directly, without a D-Bus daemon in between.
+    #[cfg(feature = "testing")]
+    pub(crate) fn peer(mut self, stream: UnixStream) -> Self {
+        self.bus = Bus::Peer(stream);
+        self
+    }
+
+    /// Sets the D-Bus name BlueZ is reachable under (`org.bluez` by default).
+    pub fn service_name(mut self, name: impl Into<String>) -> Self {
+        self.service = name.into();
+        self
+    }
+
+    /// Connects to the D-Bus daemon and creates the [`Session`].
+    pub async fn build(self) -> Result<Session> {
+        let service = BusName::try_from(self.service)
+            .map_err(|e| Error::from(format!("invalid service name: {}", e)))?;
+        let builder = match self.bus {
+            Bus::System => ConnectionBuilder::system().map_err(Error::from)?,
+            Bus::Session => ConnectionBuilder::session().map_err(Error::from)?,
+            Bus::Address(address) => ConnectionBuilder::address(&*address).map_err(Error::from)?,
+            Bus::Stream(stream) => stream_builder(stream)?,
+            #[cfg(feature = "testing")]
+            Bus::Peer(stream) => stream_builder(stream)?.p2p(),
+        };
+        // Agents are exported after the connection is up, but zbus only waits for its object server
+        // to start listening when something is served at build time. Without this, the first call
+        // BlueZ makes to a freshly registered agent can be dropped.
+        let builder = builder
+            .serve_at("/blues", ObjectManager)
+            .map_err(Error::from)?;
+        let conn = builder.build().await.map_err(Error::from)?;
+        let cache = ObjectCache::new(&conn, service.clone()).await?;
+
+        Ok(Session {
+            conn,
+            service,
+            cache,
+            notify_refs: Arc::default(),
+            discovery_refs: Arc::default(),
+        })
+    }
+}
+
+/// Creates a [`ConnectionBuilder`] for an established connection.
+///
+/// When the `tokio` feature is enabled, this has to be called from within a tokio runtime.
+pub(crate) fn stream_builder(stream: UnixStream) -> Result<ConnectionBuilder<'static>> {
+    #[cfg(feature = "tokio")]
+    let stream = {
+        stream.set_nonblocking(true).map_err(Error::from)?;
+        tokio::net::UnixStream::from_std(stream).map_err(Error::from)?
+    };
+    Ok(ConnectionBuilder::unix_stream(stream))
 }
@@ -0,0 +1,307 @@
+//! Per-object reference counting, shared by all clones of a [`Session`].
+//!
+//! BlueZ tracks some state (like active notifications or discovery) only once per D-Bus
+//! connection, so the D-Bus calls that enable and disable it have to be coordinated between all
+//! users within a [`Session`].
+//!
+//! [`Session`]: crate::Session
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+use futures_util::lock::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+use crate::{executor, Result};
+
+type RefMap = Mutex<HashMap<String, Arc<Refs>>>;
+
+/// Reference counts keyed by D-Bus object path.
+#[derive(Default)]
+pub(crate) struct RefCounts {
+    /// Entries are removed once they are unused, so that the map doesn't grow with every object
+    /// that was ever used.
+    refs: Arc<RefMap>,
+}
+
+#[derive(Default)]
+struct Refs {
+    count: Mutex<usize>,
+    /// Whether the state is currently enabled on the BlueZ side.
+    ///
+    /// The lock is held across the D-Bus calls that enable or disable the state, which serializes
+    /// them and makes concurrent callers wait for their outcome.
+    enabled: Arc<AsyncMutex<bool>>,
+}
+
+impl RefCounts {
+    fn get(&self, path: &str) -> Entry {
+        let mut refs = self.refs.lock().unwrap();
+        Entry {
+            map: self.refs.clone(),
+            path: path.to_string(),
+            refs: Some(refs.entry(path.to_string()).or_default().clone()),
+        }
+    }
+
+    /// Takes a reference to `path`, awaiting `start` first if the state isn't enabled yet.
+    ///
+    /// If `start` fails, no reference is taken. If the returned future is dropped while `start` is
+    /// in progress, the reference is released again, and `stop` is run in the background (since
+    /// `start` may have taken effect regardless).
+    pub(crate) async fn acquire<S, T>(&self, path: &str, start: S, stop: T) -> Result<()>
+    where
+        S: Future<Output = Result<()>>,
+        T: Future<Output = Result<()>> + Send + 'static,
+    {
+        let refs = self.get(path);
+        let enabled = refs.enabled.clone().lock_owned().await;
+        *refs.count.lock().unwrap() += 1;
+        if *enabled {
+            return Ok(());
+        }
+
+        let mut cancel = CancelGuard {
+            refs: Some(refs),
+            enabled: Some(enabled),
+            stop: Some(stop),
+        };
+        let res = start.await;
+        let mut enabled = cancel.enabled.take().unwrap();
+        match res {
+            Ok(()) => *enabled = true,
+            Err(_) => cancel.refs.as_ref().unwrap().release(),
+        }
+        res
+    }
+
+    /// Releases a reference to `path`, and awaits `stop` if it was the last one.
+    pub(crate) async fn release<T>(&self, path: &str, stop: T) -> Result<()>
+    where
+        T: Future<Output = Result<()>>,
+    {
+        let refs = self.get(path);
+        refs.release();
+        let mut enabled = refs.enabled.lock().await;
+        refs.stop_if_unused(&mut enabled, stop).await
+    }
+
+    /// Releases a reference to `path` like [`RefCounts::release`], but runs `stop` in the
+    /// background, only logging errors.
+    pub(crate) fn release_in_background<T>(&self, path: &str, stop: T)
+    where
+        T: Future<Output = Result<()>> + Send + 'static,
+    {
+        let refs = self.get(path);
+        refs.release();
+        executor::spawn_detached(async move {
+            let mut enabled = refs.enabled.lock().await;
+            if let Err(e) = refs.stop_if_unused(&mut enabled, stop).await {
+                log::warn!("failed to release {}: {}", refs.path, e);
+            }
+        });
+    }
+}
+
+impl Refs {
+    fn release(&self) {
+        let mut count = self.count.lock().unwrap();
+        *count = count.saturating_sub(1);
+    }
+
+    /// Awaits `stop` if the state is enabled but no references are left.
+    ///
+    /// Must be called with the `enabled` lock held.
+    async fn stop_if_unused<T>(&self, enabled: &mut bool, stop: T) -> Result<()>
+    where
+        T: Future<Output = Result<()>>,
+    {
+        if *enabled && *self.count.lock().unwrap() == 0 {
+            *enabled = false;
+            stop.await?;
+        }
+        Ok(())
+    }
+}
+
+/// A handle to the [`Refs`] of a path, which removes them from the map when dropped last.
+///
+/// Any guard of the `enabled` lock has to be dropped before the [`Entry`] it was obtained from.
+struct Entry {
+    map: Arc<RefMap>,
+    path: String,
+    /// Only [`None`] while being dropped.
+    refs: Option<Arc<Refs>>,
+}
+
+impl Deref for Entry {
+    type Target = Refs;
+
+    fn deref(&self) -> &Refs {
+        self.refs.as_ref().unwrap()
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        // Entries are only created and dropped with the map locked, so the strong count can't
+        // change while we hold the lock.
+        let mut map = self.map.lock().unwrap();
+        let refs = self.refs.take().unwrap();
+        let unused = Arc::strong_count(&refs) == 2
+            && *refs.count.lock().unwrap() == 0
+            && refs.enabled.try_lock().is_some_and(|enabled| !*enabled);
+        if unused {
+            map.remove(&self.path);
+        }
+        drop(refs);
+    }
+}
+
+/// Cleans up after a cancelled [`RefCounts::acquire`].
+struct CancelGuard<T>
+where
+    T: Future<Output = Result<()>> + Send + 'static,
+{
+    refs: Option<Entry>,
+    /// [`None`] once `start` has completed.
+    enabled: Option<OwnedMutexGuard<bool>>,
+    stop: Option<T>,
+}
+
+impl<T> Drop for CancelGuard<T>
+where
+    T: Future<Output = Result<()>> + Send + 'static,
+{
+    fn drop(&mut self) {
+        if let (Some(mut enabled), Some(stop)) = (self.enabled.take(), self.stop.take()) {
+            let refs = self.refs.take().unwrap();
+            refs.release();
+            // Keep holding the lock until `stop` is done, so that nobody relies on the outcome of
+            // the cancelled `start`.
+            executor::spawn_detached(async move {
+                *enabled = true;
+                if let Err(e) = refs.stop_if_unused(&mut enabled, stop).await {
+                    log::debug!("failed to undo cancelled start of {}: {}", refs.path, e);
+                }
+                drop(enabled);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::pin,
+        sync::atomic::{AtomicUsize, Ordering},
+        task::Context,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use futures_util::{future, task::noop_waker};
+
+    use super::*;
+    use crate::Error;
+
+    #[derive(Default)]
+    struct Calls {
+        starts: AtomicUsize,
+        stops: AtomicUsize,
+    }
+
+    impl Calls {
+        async fn start(&self) -> Result<()> {
+            self.starts.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn stop(self: &Arc<Self>) -> impl Future<Output = Result<()>> + Send + 'static {
+            let this = self.clone();
+            async move {
+                this.stops.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        fn get(&self) -> (usize, usize) {
+            (
+                self.starts.load(Ordering::SeqCst),
+                self.stops.load(Ordering::SeqCst),
+            )
+        }
+    }
+
+    #[test]
+    fn acquire_release() {
+        pollster::block_on(async {
+            let refs = RefCounts::default();
+            let calls = Arc::new(Calls::default());
+            refs.acquire("/a", calls.start(), calls.stop())
+                .await
+                .unwrap();
+            refs.acquire("/a", calls.start(), calls.stop())
+                .await
+                .unwrap();
+            assert_eq!(calls.get(), (1, 0));
+
+            refs.release("/a", calls.stop()).await.unwrap();
+            assert_eq!(calls.get(), (1, 0));
+            refs.release("/a", calls.stop()).await.unwrap();
+            assert_eq!(calls.get(), (1, 1));
+            assert!(refs.refs.lock().unwrap().is_empty());
+
+            refs.acquire("/a", calls.start(), calls.stop())
+                .await
+                .unwrap();
+            assert_eq!(calls.get(), (2, 1));
+        });
+    }
+
+    #[test]
+    fn failed_start() {
+        pollster::block_on(async {
+            let refs = RefCounts::default();
+            let calls = Arc::new(Calls::default());
+            let failing = future::ready(Err(Error::from("start failed")));
+            assert!(refs.acquire("/a", failing, calls.stop()).await.is_err());
+            assert!(refs.refs.lock().unwrap().is_empty());
+
+            refs.acquire("/a", calls.start(), calls.stop())
+                .await
+                .unwrap();
+            refs.release("/a", calls.stop()).await.unwrap();
+            assert_eq!(calls.get(), (1, 1));
+        });
+    }
+
+    #[test]
+    fn cancelled_start() {
+        let refs = RefCounts::default();
+        let calls = Arc::new(Calls::default());
+        {
+            let acquire = pin!(refs.acquire("/a", future::pending(), calls.stop()));
+            let waker = noop_waker();
+            let poll = acquire.poll(&mut Context::from_waker(&waker));
+            assert!(poll.is_pending());
+        }
+
+        // The cancelled `start` is undone in the background.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while calls.get() != (0, 1) || !refs.refs.lock().unwrap().is_empty() {
+            assert!(Instant::now() < deadline, "stop was not called");
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        pollster::block_on(async {
+            refs.acquire("/a", calls.start(), calls.stop())
+                .await
+                .unwrap();
+            assert_eq!(calls.get(), (1, 1));
+        });
+    }
+}
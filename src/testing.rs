@@ -0,0 +1,1805 @@
+//! An in-process mock of BlueZ, for testing code built on this library without Bluetooth hardware.
+//!
+//! [`MockBluez`] exports a fake `org.bluez` object tree over an in-process socket pair.
+//! [`Session`]s obtained via [`MockBluez::session`] talk to this fake instead of the system's
+//! BlueZ.
+//!
+//! The object tree is scripted via handles like [`MockAdapter`] and [`MockDevice`], which allow
+//! adding devices and GATT attributes, changing advertised data, pushing notifications, and
+//! injecting errors. Pairing requests can be sent to registered agents via [`MockAgent`].
+//!
+//! This module is only available when the `testing` Cargo feature is enabled.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io::{BufRead, BufReader},
+    mem,
+    os::{
+        fd::{AsRawFd, FromRawFd},
+        unix::net::{UnixDatagram, UnixStream},
+    },
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use futures_util::future::try_join;
+use zbus::{
+    dbus_interface,
+    export::serde::{de::DeserializeOwned, Serialize},
+    fdo::ObjectManager,
+    names::ErrorName,
+    zvariant::{DynamicType, OwnedFd, OwnedObjectPath, OwnedValue, Type, Value},
+    Connection, ConnectionBuilder, DBusError, Guid, Interface, InterfaceRef, Message,
+    MessageBuilder, MessageHeader, SignalContext,
+};
+
+use crate::{address::Address, uuid::Uuid, Error, Result, Session};
+
+/// A D-Bus error reply with an arbitrary, scripted error name.
+#[derive(Debug)]
+struct MockError {
+    name: String,
+    message: String,
+}
+
+impl MockError {
+    fn new(name: &str, message: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl DBusError for MockError {
+    fn create_reply(&self, call: &MessageHeader<'_>) -> zbus::Result<Message> {
+        MessageBuilder::error(call, self.name())?.build(&(self.message.as_str(),))
+    }
+
+    fn name(&self) -> ErrorName<'_> {
+        ErrorName::from_str_unchecked(&self.name)
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some(&self.message)
+    }
+}
+
+impl From<zbus::Error> for MockError {
+    fn from(e: zbus::Error) -> Self {
+        Self::new("org.bluez.Error.Failed", &e.to_string())
+    }
+}
+
+/// A fake BlueZ object tree.
+///
+/// Dropping the [`MockBluez`] shuts it down.
+pub struct MockBluez {
+    conn: Connection,
+    transport: Transport,
+}
+
+enum Transport {
+    /// The other end of the in-process connection.
+    Peer(Session),
+    /// A private D-Bus daemon that the object tree is exported on.
+    Bus(Daemon),
+}
+
+impl MockBluez {
+    /// Exports an empty BlueZ object tree over an in-process socket pair.
+    ///
+    /// When the `tokio` feature is enabled, this has to be called from within a tokio runtime.
+    pub async fn new() -> Result<Self> {
+        let (server, client) = UnixStream::pair().map_err(Error::from)?;
+        let guid = Guid::generate();
+        // Both ends have to be built concurrently, since they perform a handshake.
+        let server = async {
+            crate::stream_builder(server)?
+                .server(&guid)
+                .p2p()
+                .serve_at("/", ObjectManager)
+                .map_err(Error::from)?
+                .serve_at(AGENT_MANAGER_PATH, AgentManagerMock::default())
+                .map_err(Error::from)?
+                .build()
+                .await
+                .map_err(Error::from)
+        };
+        let client = Session::builder().peer(client).build();
+        let (conn, session) = try_join(server, client).await?;
+        Ok(Self {
+            conn,
+            transport: Transport::Peer(session),
+        })
+    }
+
+    /// Starts a private D-Bus daemon and exports an empty BlueZ object tree on it, under the name
+    /// `org.bluez`.
+    ///
+    /// This requires `dbus-daemon` to be installed. Unlike [`MockBluez::new`], this allows
+    /// connecting to the mock via [`MockBluez::address`], eg. to test a custom
+    /// [`SessionBuilder::service_name`]. Like [`MockBluez::new`], this has to be called from within a
+    /// tokio runtime when the `tokio` feature is enabled.
+    ///
+    /// [`SessionBuilder::service_name`]: crate::SessionBuilder::service_name
+    pub async fn with_bus() -> Result<Self> {
+        let daemon = Daemon::start()?;
+        log::debug!("started mock D-Bus daemon at {}", daemon.address);
+        let conn = ConnectionBuilder::address(&*daemon.address)
+            .map_err(Error::from)?
+            .name("org.bluez")
+            .map_err(Error::from)?
+            .serve_at("/", ObjectManager)
+            .map_err(Error::from)?
+            .build()
+            .await
+            .map_err(Error::from)?;
+        // Adding this in the builder would emit `InterfacesAdded` before the `Hello` call, which
+        // makes the daemon drop the connection.
+        conn.object_server()
+            .at(AGENT_MANAGER_PATH, AgentManagerMock::default())
+            .await
+            .map_err(Error::from)?;
+        Ok(Self {
+            conn,
+            transport: Transport::Bus(daemon),
+        })
+    }
+
+    /// Returns the D-Bus address of the private D-Bus daemon, if this [`MockBluez`] was created
+    /// via [`MockBluez::with_bus`].
+    pub fn address(&self) -> Option<&str> {
+        match &self.transport {
+            Transport::Peer(_) => None,
+            Transport::Bus(daemon) => Some(&daemon.address),
+        }
+    }
+
+    /// Releases the `org.bluez` name, as if the BlueZ daemon had stopped.
+    ///
+    /// The object tree is kept and can still be modified, but clients won't see any changes until
+    /// [`MockBluez::start_service`] is called. This is only supported if this [`MockBluez`] was
+    /// created via [`MockBluez::with_bus`].
+    pub async fn stop_service(&self) -> Result<()> {
+        if self.address().is_none() {
+            return Err(Error::from("`stop_service` requires `MockBluez::with_bus`"));
+        }
+        self.conn
+            .release_name("org.bluez")
+            .await
+            .map(drop)
+            .map_err(Error::from)
+    }
+
+    /// Acquires the `org.bluez` name again after [`MockBluez::stop_service`], as if the BlueZ
+    /// daemon had been restarted.
+    pub async fn start_service(&self) -> Result<()> {
+        if self.address().is_none() {
+            return Err(Error::from(
+                "`start_service` requires `MockBluez::with_bus`",
+            ));
+        }
+        self.conn
+            .request_name("org.bluez")
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Returns a [`Session`] that talks to this [`MockBluez`].
+    ///
+    /// If this [`MockBluez`] was created via [`MockBluez::new`], all returned [`Session`]s share the
+    /// same connection.
+    pub async fn session(&self) -> Result<Session> {
+        match &self.transport {
+            Transport::Peer(session) => Ok(session.clone()),
+            Transport::Bus(daemon) => Session::builder().address(&*daemon.address).build().await,
+        }
+    }
+
+    /// Adds an adapter with the given device name (eg. `hci0`) and [`Address`].
+    ///
+    /// The adapter starts out powered on.
+    pub async fn add_adapter(&self, name: &str, address: Address) -> Result<MockAdapter> {
+        let path = owned_path(format!("/org/bluez/{}", name))?;
+        let iface = AdapterMock {
+            ctxt: SignalContext::from_parts(self.conn.clone(), path.clone().into_inner()),
+            address: address.to_string(),
+            name: name.to_string(),
+            alias: name.to_string(),
+            powered: true,
+            discoverable: false,
+            discoverable_timeout: 180,
+            pairable: true,
+            pairable_timeout: 0,
+            connectable: true,
+            discovering: false,
+            discovery_filter: MockDiscoveryFilter::default(),
+        };
+        add_object(&self.conn, &path, iface).await?;
+
+        Ok(MockAdapter {
+            conn: self.conn.clone(),
+            path,
+        })
+    }
+
+    /// Returns the agent BlueZ would send pairing requests to, or [`None`] if no agent is
+    /// registered.
+    ///
+    /// Like in BlueZ, this is the default agent if one was requested, and the most recently
+    /// registered agent otherwise.
+    pub async fn agent(&self) -> Result<Option<MockAgent>> {
+        let manager = self
+            .conn
+            .object_server()
+            .interface::<_, AgentManagerMock>(AGENT_MANAGER_PATH)
+            .await
+            .map_err(Error::from)?;
+        let manager = manager.get().await;
+        let agent = manager
+            .agents
+            .iter()
+            .find(|agent| agent.default)
+            .or_else(|| manager.agents.last());
+        Ok(agent.map(|agent| MockAgent {
+            conn: self.conn.clone(),
+            agent: agent.clone(),
+        }))
+    }
+}
+
+/// A `dbus-daemon` process and its temporary directory, removed on drop.
+struct Daemon {
+    dir: PathBuf,
+    child: Option<Child>,
+    address: String,
+}
+
+impl Daemon {
+    fn start() -> Result<Self> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("blues-mock-{}-{}", std::process::id(), id));
+        fs::create_dir_all(&dir).map_err(Error::from)?;
+        let mut this = Self {
+            dir,
+            child: None,
+            address: String::new(),
+        };
+
+        let config = this.dir.join("bus.conf");
+        fs::write(
+            &config,
+            format!(
+                r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:dir={}</listen>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>
+"#,
+                this.dir.display()
+            ),
+        )
+        .map_err(Error::from)?;
+
+        let child = this.child.insert(
+            Command::new("dbus-daemon")
+                .arg(format!("--config-file={}", config.display()))
+                .arg("--nofork")
+                .arg("--print-address")
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .spawn()
+                .map_err(|e| Error::from(format!("failed to start `dbus-daemon`: {}", e)))?,
+        );
+
+        // The daemon prints its address once it is ready to accept connections.
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let n = BufReader::new(stdout)
+            .read_line(&mut this.address)
+            .map_err(Error::from)?;
+        if n == 0 {
+            return Err(Error::from(
+                "`dbus-daemon` exited without printing its address",
+            ));
+        }
+        this.address.truncate(this.address.trim_end().len());
+
+        Ok(this)
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        if let Some(child) = &mut self.child {
+            child.kill().ok();
+            child.wait().ok();
+        }
+        fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+fn owned_path(path: String) -> Result<OwnedObjectPath> {
+    OwnedObjectPath::try_from(path).map_err(|e| Error::from(format!("invalid object path: {}", e)))
+}
+
+/// Returns a connected `SOCK_SEQPACKET` pair, like BlueZ hands out for `AcquireNotify` and
+/// `AcquireWrite`.
+fn seqpacket_pair() -> std::result::Result<(OwnedFd, UnixDatagram), MockError> {
+    let mut fds = [0; 2];
+    // Safety: `fds` has room for the two file descriptors.
+    let res = unsafe {
+        libc::socketpair(
+            libc::AF_UNIX,
+            libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC,
+            0,
+            fds.as_mut_ptr(),
+        )
+    };
+    if res != 0 {
+        let e = std::io::Error::last_os_error();
+        return Err(MockError::new("org.bluez.Error.Failed", &e.to_string()));
+    }
+    // Safety: both file descriptors were just created and are owned by nothing else.
+    unsafe {
+        Ok((
+            OwnedFd::from_raw_fd(fds[0]),
+            UnixDatagram::from_raw_fd(fds[1]),
+        ))
+    }
+}
+
+async fn add_object<I: Interface>(
+    conn: &Connection,
+    path: &OwnedObjectPath,
+    iface: I,
+) -> Result<()> {
+    let added = conn
+        .object_server()
+        .at(path, iface)
+        .await
+        .map_err(Error::from)?;
+    if added {
+        Ok(())
+    } else {
+        Err(Error::from(format!("object {} already exists", path)))
+    }
+}
+
+async fn interface<I: Interface>(
+    conn: &Connection,
+    path: &OwnedObjectPath,
+) -> Result<InterfaceRef<I>> {
+    conn.object_server()
+        .interface(path)
+        .await
+        .map_err(Error::from)
+}
+
+/// Removes all objects at or below `path`, deepest first.
+async fn remove_tree(conn: &Connection, path: &str) {
+    let server = conn.object_server();
+    let device = server.interface::<_, DeviceMock>(path).await;
+    let mut objects = match device {
+        Ok(device) => device.get().await.objects.clone(),
+        Err(_) => Vec::new(),
+    };
+    objects.reverse();
+    for (path, kind) in objects {
+        let res = match kind {
+            ObjectKind::Service => server.remove::<ServiceMock, _>(&path).await,
+            ObjectKind::Characteristic => server.remove::<CharacteristicMock, _>(&path).await,
+            ObjectKind::Descriptor => server.remove::<DescriptorMock, _>(&path).await,
+        };
+        if let Err(e) = res {
+            log::warn!("failed to remove mock object {}: {}", path, e);
+        }
+    }
+    if let Err(e) = server.remove::<DeviceMock, _>(path).await {
+        log::warn!("failed to remove mock object {}: {}", path, e);
+    }
+}
+
+struct AdapterMock {
+    ctxt: SignalContext<'static>,
+    address: String,
+    name: String,
+    alias: String,
+    powered: bool,
+    discoverable: bool,
+    discoverable_timeout: u32,
+    pairable: bool,
+    pairable_timeout: u32,
+    connectable: bool,
+    discovering: bool,
+    discovery_filter: MockDiscoveryFilter,
+}
+
+#[dbus_interface(name = "org.bluez.Adapter1")]
+impl AdapterMock {
+    async fn start_discovery(&mut self) -> std::result::Result<(), MockError> {
+        if !self.powered {
+            return Err(MockError::new(
+                "org.bluez.Error.NotReady",
+                "Resource Not Ready",
+            ));
+        }
+        if self.discovering {
+            return Err(MockError::new(
+                "org.bluez.Error.InProgress",
+                "Operation already in progress",
+            ));
+        }
+        self.discovering = true;
+        self.discovering_changed(&self.ctxt).await?;
+        Ok(())
+    }
+
+    async fn stop_discovery(&mut self) -> std::result::Result<(), MockError> {
+        if !self.discovering {
+            return Err(MockError::new(
+                "org.bluez.Error.Failed",
+                "No discovery started",
+            ));
+        }
+        self.discovering = false;
+        self.discovering_changed(&self.ctxt).await?;
+        Ok(())
+    }
+
+    async fn set_discovery_filter(
+        &mut self,
+        filter: HashMap<String, OwnedValue>,
+    ) -> std::result::Result<(), MockError> {
+        self.discovery_filter = MockDiscoveryFilter::parse(&filter).ok_or_else(|| {
+            MockError::new(
+                "org.bluez.Error.InvalidArguments",
+                "Invalid arguments in method call",
+            )
+        })?;
+        Ok(())
+    }
+
+    async fn get_discovery_filters(&self) -> Vec<String> {
+        [
+            "UUIDs",
+            "RSSI",
+            "Pathloss",
+            "Transport",
+            "DuplicateData",
+            "Discoverable",
+            "Pattern",
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    async fn connect_device(
+        &self,
+        properties: HashMap<String, OwnedValue>,
+    ) -> std::result::Result<OwnedObjectPath, MockError> {
+        let invalid = || {
+            MockError::new(
+                "org.bluez.Error.InvalidArguments",
+                "Invalid arguments in method call",
+            )
+        };
+        let address = match properties.get("Address").map(|value| &**value) {
+            Some(Value::Str(address)) => address.parse::<Address>().map_err(|_| invalid())?,
+            _ => return Err(invalid()),
+        };
+        let address_type = match properties.get("AddressType").map(|value| &**value) {
+            None => "public",
+            Some(Value::Str(ty)) if ty.as_str() == "public" => "public",
+            Some(Value::Str(ty)) if ty.as_str() == "random" => "random",
+            Some(_) => return Err(invalid()),
+        };
+
+        let conn = self.ctxt.connection();
+        let adapter = OwnedObjectPath::from(self.ctxt.path().to_owned());
+        let path = device_path(&adapter, address)
+            .map_err(|e| MockError::new("org.bluez.Error.Failed", &e.to_string()))?;
+        let mut device = DeviceMock::new(conn, &adapter, &path, address, address_type);
+        device.connected = true;
+        device.services_resolved = true;
+        if !conn.object_server().at(&path, device).await? {
+            return Err(MockError::new(
+                "org.bluez.Error.AlreadyExists",
+                "Already Exists",
+            ));
+        }
+        Ok(path)
+    }
+
+    async fn remove_device(&self, device: OwnedObjectPath) -> std::result::Result<(), MockError> {
+        if !device.starts_with(&format!("{}/", self.ctxt.path())) {
+            return Err(MockError::new(
+                "org.bluez.Error.InvalidArguments",
+                "Invalid arguments in method call",
+            ));
+        }
+
+        remove_tree(self.ctxt.connection(), device.as_str()).await;
+        Ok(())
+    }
+
+    #[dbus_interface(property)]
+    fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn address_type(&self) -> String {
+        "public".into()
+    }
+
+    #[dbus_interface(property)]
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn alias(&self) -> String {
+        self.alias.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn set_alias(&mut self, alias: String) {
+        self.alias = if alias.is_empty() {
+            self.name.clone()
+        } else {
+            alias
+        };
+    }
+
+    #[dbus_interface(property)]
+    fn class(&self) -> u32 {
+        0
+    }
+
+    #[dbus_interface(property)]
+    fn powered(&self) -> bool {
+        self.powered
+    }
+
+    #[dbus_interface(property)]
+    async fn set_powered(&mut self, powered: bool) {
+        self.powered = powered;
+        if let Err(e) = self.power_state_changed(&self.ctxt).await {
+            log::warn!("failed to emit PowerState change: {}", e);
+        }
+    }
+
+    #[dbus_interface(property)]
+    fn power_state(&self) -> String {
+        if self.powered { "on" } else { "off" }.into()
+    }
+
+    #[dbus_interface(property)]
+    fn discoverable(&self) -> bool {
+        self.discoverable
+    }
+
+    #[dbus_interface(property)]
+    fn set_discoverable(&mut self, discoverable: bool) {
+        self.discoverable = discoverable;
+    }
+
+    #[dbus_interface(property)]
+    fn discoverable_timeout(&self) -> u32 {
+        self.discoverable_timeout
+    }
+
+    #[dbus_interface(property)]
+    fn set_discoverable_timeout(&mut self, timeout: u32) {
+        self.discoverable_timeout = timeout;
+    }
+
+    #[dbus_interface(property)]
+    fn pairable(&self) -> bool {
+        self.pairable
+    }
+
+    #[dbus_interface(property)]
+    fn set_pairable(&mut self, pairable: bool) {
+        self.pairable = pairable;
+    }
+
+    #[dbus_interface(property)]
+    fn pairable_timeout(&self) -> u32 {
+        self.pairable_timeout
+    }
+
+    #[dbus_interface(property)]
+    fn set_pairable_timeout(&mut self, timeout: u32) {
+        self.pairable_timeout = timeout;
+    }
+
+    #[dbus_interface(property)]
+    fn connectable(&self) -> bool {
+        self.connectable
+    }
+
+    #[dbus_interface(property)]
+    fn set_connectable(&mut self, connectable: bool) {
+        self.connectable = connectable;
+    }
+
+    #[dbus_interface(property)]
+    fn discovering(&self) -> bool {
+        self.discovering
+    }
+}
+
+/// A handle to a fake adapter exported by [`MockBluez`].
+pub struct MockAdapter {
+    conn: Connection,
+    path: OwnedObjectPath,
+}
+
+impl MockAdapter {
+    /// Adds a device with the given [`Address`], as if it had been discovered.
+    pub async fn add_device(&self, address: Address) -> Result<MockDevice> {
+        let path = device_path(&self.path, address)?;
+        let iface = DeviceMock::new(&self.conn, &self.path, &path, address, "public");
+        add_object(&self.conn, &path, iface).await?;
+
+        Ok(MockDevice {
+            conn: self.conn.clone(),
+            path,
+        })
+    }
+
+    /// Returns whether the adapter is currently powered on.
+    pub async fn is_powered(&self) -> Result<bool> {
+        let adapter = interface::<AdapterMock>(&self.conn, &self.path).await?;
+        let powered = adapter.get().await.powered;
+        Ok(powered)
+    }
+
+    /// Returns whether the adapter is currently performing discovery.
+    pub async fn is_discovering(&self) -> Result<bool> {
+        let adapter = interface::<AdapterMock>(&self.conn, &self.path).await?;
+        let discovering = adapter.get().await.discovering;
+        Ok(discovering)
+    }
+
+    /// Returns the discovery filter most recently set via `SetDiscoveryFilter`.
+    pub async fn discovery_filter(&self) -> Result<MockDiscoveryFilter> {
+        let adapter = interface::<AdapterMock>(&self.conn, &self.path).await?;
+        let filter = adapter.get().await.discovery_filter.clone();
+        Ok(filter)
+    }
+
+    /// Removes the adapter (and all of its devices), as if it had been unplugged.
+    pub async fn remove(self) -> Result<()> {
+        let server = self.conn.object_server();
+        let prefix = format!("{}/", self.path.as_str());
+        let devices = device_paths(&self.conn, &prefix).await?;
+        for device in devices {
+            remove_tree(&self.conn, &device).await;
+        }
+        server
+            .remove::<AdapterMock, _>(&self.path)
+            .await
+            .map_err(Error::from)?;
+        Ok(())
+    }
+}
+
+/// A discovery filter, as received by the mock adapter's `SetDiscoveryFilter` method.
+///
+/// Returned by [`MockAdapter::discovery_filter`]. Fields that weren't set are [`None`] (or
+/// empty).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MockDiscoveryFilter {
+    /// The `UUIDs` entry.
+    pub uuids: Vec<Uuid>,
+    /// The `RSSI` entry.
+    pub rssi: Option<i16>,
+    /// The `Pathloss` entry.
+    pub pathloss: Option<u16>,
+    /// The `Transport` entry (`auto`, `bredr` or `le`).
+    pub transport: Option<String>,
+    /// The `DuplicateData` entry.
+    pub duplicate_data: Option<bool>,
+    /// The `Discoverable` entry.
+    pub discoverable: Option<bool>,
+    /// The `Pattern` entry.
+    pub pattern: Option<String>,
+}
+
+impl MockDiscoveryFilter {
+    /// Parses the filter dictionary, returning [`None`] if BlueZ would reject it.
+    fn parse(filter: &HashMap<String, OwnedValue>) -> Option<Self> {
+        let mut this = Self::default();
+        for (key, value) in filter {
+            match (key.as_str(), &**value) {
+                ("UUIDs", Value::Array(uuids)) => {
+                    for uuid in uuids.iter() {
+                        let Value::Str(uuid) = uuid else { return None };
+                        this.uuids.push(uuid.parse().ok()?);
+                    }
+                }
+                ("RSSI", Value::I16(rssi)) => this.rssi = Some(*rssi),
+                ("Pathloss", Value::U16(pathloss)) => this.pathloss = Some(*pathloss),
+                ("Transport", Value::Str(transport))
+                    if ["auto", "bredr", "le"].contains(&transport.as_str()) =>
+                {
+                    this.transport = Some(transport.to_string());
+                }
+                ("DuplicateData", Value::Bool(b)) => this.duplicate_data = Some(*b),
+                ("Discoverable", Value::Bool(b)) => this.discoverable = Some(*b),
+                ("Pattern", Value::Str(pattern)) => this.pattern = Some(pattern.to_string()),
+                _ => return None,
+            }
+        }
+        // Like BlueZ, reject filters that use both RSSI and path loss.
+        if this.rssi.is_some() && this.pathloss.is_some() {
+            return None;
+        }
+        Some(this)
+    }
+}
+
+fn device_path(adapter: &str, address: Address) -> Result<OwnedObjectPath> {
+    owned_path(format!(
+        "{}/dev_{}",
+        adapter,
+        address.to_string().replace(':', "_")
+    ))
+}
+
+/// Returns the paths of all devices below `prefix`, since the object server can't be enumerated.
+async fn device_paths(conn: &Connection, prefix: &str) -> Result<Vec<String>> {
+    let manager = zbus::fdo::ObjectManagerProxy::builder(conn)
+        .destination("org.bluez")
+        .map_err(Error::from)?
+        .path("/")
+        .map_err(Error::from)?
+        .build()
+        .await
+        .map_err(Error::from)?;
+    let objects = manager.get_managed_objects().await.map_err(Error::from)?;
+    Ok(objects
+        .into_iter()
+        .filter(|(path, intfs)| path.starts_with(prefix) && intfs.contains_key("org.bluez.Device1"))
+        .map(|(path, _)| path.to_string())
+        .collect())
+}
+
+#[derive(Clone, Copy)]
+enum ObjectKind {
+    Service,
+    Characteristic,
+    Descriptor,
+}
+
+struct DeviceMock {
+    ctxt: SignalContext<'static>,
+    adapter: OwnedObjectPath,
+    address: String,
+    address_type: String,
+    alias: String,
+    rssi: i16,
+    tx_power: i16,
+    uuids: Vec<String>,
+    manufacturer_data: HashMap<u16, Vec<u8>>,
+    service_data: HashMap<String, Vec<u8>>,
+    connected: bool,
+    services_resolved: bool,
+    paired: bool,
+    trusted: bool,
+    blocked: bool,
+    wake_allowed: bool,
+    /// Errors to return from the next `Connect` calls.
+    connect_errors: VecDeque<MockError>,
+    /// GATT objects below this device, in creation order.
+    objects: Vec<(OwnedObjectPath, ObjectKind)>,
+    next_handle: u16,
+}
+
+impl DeviceMock {
+    fn new(
+        conn: &Connection,
+        adapter: &OwnedObjectPath,
+        path: &OwnedObjectPath,
+        address: Address,
+        address_type: &str,
+    ) -> Self {
+        Self {
+            ctxt: SignalContext::from_parts(conn.clone(), path.clone().into_inner()),
+            adapter: adapter.clone(),
+            address: address.to_string(),
+            address_type: address_type.to_string(),
+            alias: address.to_string().replace(':', "-"),
+            rssi: -60,
+            tx_power: 0,
+            uuids: Vec::new(),
+            manufacturer_data: HashMap::new(),
+            service_data: HashMap::new(),
+            connected: false,
+            services_resolved: false,
+            paired: false,
+            trusted: false,
+            blocked: false,
+            wake_allowed: false,
+            connect_errors: VecDeque::new(),
+            objects: Vec::new(),
+            next_handle: 1,
+        }
+    }
+
+    async fn set_connected(&mut self, connected: bool) -> zbus::Result<()> {
+        if !connected {
+            // Like BlueZ, close the sockets handed out by `AcquireNotify` and `AcquireWrite`.
+            let server = self.ctxt.connection().object_server();
+            for (path, kind) in &self.objects {
+                if let ObjectKind::Characteristic = kind {
+                    let characteristic = server.interface::<_, CharacteristicMock>(path).await?;
+                    let mut characteristic = characteristic.get_mut().await;
+                    characteristic.notify_io = None;
+                    characteristic.write_io = None;
+                }
+            }
+        }
+        self.connected = connected;
+        self.connected_changed(&self.ctxt).await?;
+        self.services_resolved = connected;
+        self.services_resolved_changed(&self.ctxt).await
+    }
+}
+
+#[dbus_interface(name = "org.bluez.Device1")]
+impl DeviceMock {
+    async fn connect(&mut self) -> std::result::Result<(), MockError> {
+        if let Some(e) = self.connect_errors.pop_front() {
+            return Err(e);
+        }
+        if self.connected {
+            return Err(MockError::new(
+                "org.bluez.Error.AlreadyConnected",
+                "Already Connected",
+            ));
+        }
+        self.set_connected(true).await?;
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> std::result::Result<(), MockError> {
+        if !self.connected {
+            return Err(MockError::new(
+                "org.bluez.Error.NotConnected",
+                "Not Connected",
+            ));
+        }
+        self.set_connected(false).await?;
+        Ok(())
+    }
+
+    async fn pair(&mut self) -> std::result::Result<(), MockError> {
+        if self.paired {
+            return Err(MockError::new(
+                "org.bluez.Error.AlreadyExists",
+                "Already Exists",
+            ));
+        }
+        self.paired = true;
+        self.paired_changed(&self.ctxt).await?;
+        self.bonded_changed(&self.ctxt).await?;
+        Ok(())
+    }
+
+    async fn cancel_pairing(&self) -> std::result::Result<(), MockError> {
+        Err(MockError::new(
+            "org.bluez.Error.DoesNotExist",
+            "Does Not Exist",
+        ))
+    }
+
+    #[dbus_interface(property)]
+    fn adapter(&self) -> OwnedObjectPath {
+        self.adapter.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn address_type(&self) -> String {
+        self.address_type.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn alias(&self) -> String {
+        self.alias.clone()
+    }
+
+    #[dbus_interface(property, name = "RSSI")]
+    fn rssi(&self) -> i16 {
+        self.rssi
+    }
+
+    #[dbus_interface(property)]
+    fn tx_power(&self) -> i16 {
+        self.tx_power
+    }
+
+    #[dbus_interface(property, name = "UUIDs")]
+    fn uuids(&self) -> Vec<String> {
+        self.uuids.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn manufacturer_data(&self) -> HashMap<u16, Value<'static>> {
+        self.manufacturer_data
+            .iter()
+            .map(|(id, data)| (*id, Value::from(data.clone())))
+            .collect()
+    }
+
+    #[dbus_interface(property)]
+    fn service_data(&self) -> HashMap<String, Value<'static>> {
+        self.service_data
+            .iter()
+            .map(|(uuid, data)| (uuid.clone(), Value::from(data.clone())))
+            .collect()
+    }
+
+    #[dbus_interface(property)]
+    fn connected(&self) -> bool {
+        self.connected
+    }
+
+    #[dbus_interface(property)]
+    fn services_resolved(&self) -> bool {
+        self.services_resolved
+    }
+
+    #[dbus_interface(property)]
+    fn paired(&self) -> bool {
+        self.paired
+    }
+
+    #[dbus_interface(property)]
+    fn bonded(&self) -> bool {
+        self.paired
+    }
+
+    #[dbus_interface(property)]
+    fn trusted(&self) -> bool {
+        self.trusted
+    }
+
+    #[dbus_interface(property)]
+    fn set_trusted(&mut self, trusted: bool) {
+        self.trusted = trusted;
+    }
+
+    #[dbus_interface(property)]
+    fn blocked(&self) -> bool {
+        self.blocked
+    }
+
+    #[dbus_interface(property)]
+    async fn set_blocked(&mut self, blocked: bool) -> zbus::fdo::Result<()> {
+        self.blocked = blocked;
+        // Like BlueZ, blocking a device disconnects it.
+        if blocked && self.connected {
+            self.set_connected(false).await?;
+        }
+        Ok(())
+    }
+
+    #[dbus_interface(property)]
+    fn legacy_pairing(&self) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn wake_allowed(&self) -> bool {
+        self.wake_allowed
+    }
+
+    #[dbus_interface(property)]
+    fn set_wake_allowed(&mut self, wake_allowed: bool) {
+        self.wake_allowed = wake_allowed;
+    }
+}
+
+/// A handle to a fake device exported by [`MockBluez`].
+pub struct MockDevice {
+    conn: Connection,
+    path: OwnedObjectPath,
+}
+
+impl MockDevice {
+    async fn interface(&self) -> Result<InterfaceRef<DeviceMock>> {
+        interface(&self.conn, &self.path).await
+    }
+
+    /// Changes the advertised name of the device.
+    pub async fn set_alias(&self, alias: &str) -> Result<()> {
+        let device = self.interface().await?;
+        let mut device = device.get_mut().await;
+        device.alias = alias.to_string();
+        device
+            .alias_changed(&device.ctxt)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Changes the RSSI of the device, as if an advertisement had been received.
+    pub async fn set_rssi(&self, rssi: i16) -> Result<()> {
+        let device = self.interface().await?;
+        let mut device = device.get_mut().await;
+        device.rssi = rssi;
+        device
+            .r_s_s_i_changed(&device.ctxt)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Changes the advertised TX power level of the device.
+    pub async fn set_tx_power(&self, tx_power: i16) -> Result<()> {
+        let device = self.interface().await?;
+        let mut device = device.get_mut().await;
+        device.tx_power = tx_power;
+        device
+            .tx_power_changed(&device.ctxt)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Changes the list of service [`Uuid`]s advertised by the device.
+    pub async fn set_service_uuids(&self, uuids: &[Uuid]) -> Result<()> {
+        let device = self.interface().await?;
+        let mut device = device.get_mut().await;
+        device.uuids = uuids.iter().map(ToString::to_string).collect();
+        device
+            .u_u_i_ds_changed(&device.ctxt)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Changes the manufacturer-specific data advertised by the device.
+    pub async fn set_manufacturer_data(&self, company: u16, data: &[u8]) -> Result<()> {
+        let device = self.interface().await?;
+        let mut device = device.get_mut().await;
+        device.manufacturer_data = HashMap::from([(company, data.to_vec())]);
+        device
+            .manufacturer_data_changed(&device.ctxt)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Changes the service data advertised by the device.
+    pub async fn set_service_data(&self, service: Uuid, data: &[u8]) -> Result<()> {
+        let device = self.interface().await?;
+        let mut device = device.get_mut().await;
+        device.service_data = HashMap::from([(service.to_string(), data.to_vec())]);
+        device
+            .service_data_changed(&device.ctxt)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// Makes the next connection attempt fail with the given D-Bus error.
+    ///
+    /// `name` is the D-Bus error name (eg. `org.bluez.Error.Failed`), and `message` the error
+    /// message (eg. `le-connection-abort-by-local`). Multiple calls queue multiple errors.
+    pub async fn fail_next_connect(&self, name: &str, message: &str) -> Result<()> {
+        let device = self.interface().await?;
+        let mut device = device.get_mut().await;
+        device
+            .connect_errors
+            .push_back(MockError::new(name, message));
+        Ok(())
+    }
+
+    /// Returns whether the device is currently connected.
+    pub async fn is_connected(&self) -> Result<bool> {
+        let device = self.interface().await?;
+        let connected = device.get().await.connected;
+        Ok(connected)
+    }
+
+    /// Returns whether the device is paired.
+    pub async fn is_paired(&self) -> Result<bool> {
+        let device = self.interface().await?;
+        let paired = device.get().await.paired;
+        Ok(paired)
+    }
+
+    /// Disconnects the device, as if the remote side had terminated the connection.
+    pub async fn disconnect(&self) -> Result<()> {
+        let device = self.interface().await?;
+        let mut device = device.get_mut().await;
+        device.set_connected(false).await.map_err(Error::from)
+    }
+
+    /// Adds a primary GATT service with the given [`Uuid`].
+    ///
+    /// Services are only visible to the [`Session`] once the device is connected.
+    pub async fn add_service(&self, uuid: Uuid) -> Result<MockService> {
+        let device = self.interface().await?;
+        let path = {
+            let mut device = device.get_mut().await;
+            let path = owned_path(format!(
+                "{}/service{:04x}",
+                self.path.as_str(),
+                device.next_handle
+            ))?;
+            device.next_handle += 1;
+            device.objects.push((path.clone(), ObjectKind::Service));
+            path
+        };
+        let iface = ServiceMock {
+            uuid: uuid.to_string(),
+            device: self.path.clone(),
+        };
+        add_object(&self.conn, &path, iface).await?;
+
+        Ok(MockService {
+            conn: self.conn.clone(),
+            device: self.path.clone(),
+            path,
+        })
+    }
+
+    /// Removes the device, as if it had disappeared.
+    pub async fn remove(self) -> Result<()> {
+        remove_tree(&self.conn, self.path.as_str()).await;
+        Ok(())
+    }
+}
+
+struct ServiceMock {
+    uuid: String,
+    device: OwnedObjectPath,
+}
+
+#[dbus_interface(name = "org.bluez.GattService1")]
+impl ServiceMock {
+    #[dbus_interface(property, name = "UUID")]
+    fn uuid(&self) -> String {
+        self.uuid.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn primary(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn device(&self) -> OwnedObjectPath {
+        self.device.clone()
+    }
+}
+
+/// A handle to a fake GATT service exported by [`MockBluez`].
+pub struct MockService {
+    conn: Connection,
+    device: OwnedObjectPath,
+    path: OwnedObjectPath,
+}
+
+impl MockService {
+    /// Adds a characteristic with the given [`Uuid`] and BlueZ flags (eg. `read`, `notify`).
+    pub async fn add_characteristic(
+        &self,
+        uuid: Uuid,
+        flags: &[&str],
+    ) -> Result<MockCharacteristic> {
+        let device = interface::<DeviceMock>(&self.conn, &self.device).await?;
+        let path = {
+            let mut device = device.get_mut().await;
+            let path = owned_path(format!(
+                "{}/char{:04x}",
+                self.path.as_str(),
+                device.next_handle
+            ))?;
+            device.next_handle += 1;
+            device
+                .objects
+                .push((path.clone(), ObjectKind::Characteristic));
+            path
+        };
+        let iface = CharacteristicMock {
+            ctxt: SignalContext::from_parts(self.conn.clone(), path.clone().into_inner()),
+            uuid: uuid.to_string(),
+            service: self.path.clone(),
+            flags: flags.iter().map(ToString::to_string).collect(),
+            value: Vec::new(),
+            max_read_len: None,
+            read_offsets: Vec::new(),
+            notifying: false,
+            notify_io: None,
+            write_io: None,
+            written: Vec::new(),
+        };
+        add_object(&self.conn, &path, iface).await?;
+
+        Ok(MockCharacteristic {
+            conn: self.conn.clone(),
+            device: self.device.clone(),
+            path,
+        })
+    }
+}
+
+/// Returns the `offset` from the options of a `ReadValue` call.
+fn read_offset(options: &HashMap<String, OwnedValue>) -> u16 {
+    match options.get("offset").map(|value| &**value) {
+        Some(Value::U16(offset)) => *offset,
+        _ => 0,
+    }
+}
+
+struct CharacteristicMock {
+    ctxt: SignalContext<'static>,
+    uuid: String,
+    service: OwnedObjectPath,
+    flags: Vec<String>,
+    value: Vec<u8>,
+    /// The maximum number of Bytes returned by a single `ReadValue` call.
+    max_read_len: Option<usize>,
+    /// The offsets of the `ReadValue` calls not yet returned by `read_offsets`.
+    read_offsets: Vec<u16>,
+    notifying: bool,
+    /// Our end of the socket handed out by `AcquireNotify`.
+    notify_io: Option<UnixDatagram>,
+    /// Our end of the socket handed out by `AcquireWrite` (non-blocking).
+    write_io: Option<UnixDatagram>,
+    written: Vec<Vec<u8>>,
+}
+
+impl CharacteristicMock {
+    fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f == flag)
+    }
+
+    /// Closes the `AcquireNotify` socket if the receiving end was closed, like BlueZ does.
+    fn check_notify_io(&mut self) {
+        let Some(io) = &self.notify_io else { return };
+        let mut pollfd = libc::pollfd {
+            fd: io.as_raw_fd(),
+            events: 0,
+            revents: 0,
+        };
+        // Safety: `pollfd` points to a single valid `pollfd` structure.
+        let res = unsafe { libc::poll(&mut pollfd, 1, 0) };
+        if res > 0 && pollfd.revents & (libc::POLLHUP | libc::POLLERR) != 0 {
+            self.notify_io = None;
+        }
+    }
+
+    /// Moves the values written to the `AcquireWrite` socket to `written`.
+    fn drain_write_io(&mut self) {
+        let Some(io) = &self.write_io else { return };
+        let mut buf = [0; 512];
+        loop {
+            match io.recv(&mut buf) {
+                Ok(n) => self.written.push(buf[..n].to_vec()),
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    self.write_io = None;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[dbus_interface(name = "org.bluez.GattCharacteristic1")]
+impl CharacteristicMock {
+    async fn read_value(
+        &mut self,
+        options: HashMap<String, OwnedValue>,
+    ) -> std::result::Result<Vec<u8>, MockError> {
+        let offset = read_offset(&options);
+        self.read_offsets.push(offset);
+        let offset = usize::from(offset);
+        if offset > self.value.len() {
+            return Err(MockError::new(
+                "org.bluez.Error.InvalidOffset",
+                "Invalid offset",
+            ));
+        }
+        let end = match self.max_read_len {
+            Some(len) => self.value.len().min(offset + len),
+            None => self.value.len(),
+        };
+        Ok(self.value[offset..end].to_vec())
+    }
+
+    async fn write_value(&mut self, value: Vec<u8>, _options: HashMap<String, OwnedValue>) {
+        self.drain_write_io();
+        self.written.push(value);
+    }
+
+    async fn acquire_write(
+        &mut self,
+        _options: HashMap<String, OwnedValue>,
+    ) -> std::result::Result<(OwnedFd, u16), MockError> {
+        if !self.has_flag("write-without-response") {
+            return Err(MockError::new(
+                "org.bluez.Error.NotSupported",
+                "Not Supported",
+            ));
+        }
+        if self.write_io.is_some() {
+            return Err(MockError::new(
+                "org.bluez.Error.NotPermitted",
+                "Write acquired",
+            ));
+        }
+        let (fd, io) = seqpacket_pair()?;
+        io.set_nonblocking(true)
+            .map_err(|e| MockError::new("org.bluez.Error.Failed", &e.to_string()))?;
+        self.write_io = Some(io);
+        Ok((fd, self.mtu()))
+    }
+
+    async fn acquire_notify(
+        &mut self,
+        _options: HashMap<String, OwnedValue>,
+    ) -> std::result::Result<(OwnedFd, u16), MockError> {
+        if !self.has_flag("notify") && !self.has_flag("indicate") {
+            return Err(MockError::new(
+                "org.bluez.Error.NotSupported",
+                "Not Supported",
+            ));
+        }
+        if self.notify_io.is_some() {
+            return Err(MockError::new(
+                "org.bluez.Error.NotPermitted",
+                "Notify acquired",
+            ));
+        }
+        if self.notifying {
+            return Err(MockError::new(
+                "org.bluez.Error.Failed",
+                "Notify already enabled",
+            ));
+        }
+        let (fd, io) = seqpacket_pair()?;
+        self.notify_io = Some(io);
+        Ok((fd, self.mtu()))
+    }
+
+    async fn start_notify(&mut self) -> std::result::Result<(), MockError> {
+        self.check_notify_io();
+        if self.notify_io.is_some() {
+            return Err(MockError::new(
+                "org.bluez.Error.NotPermitted",
+                "Notify acquired",
+            ));
+        }
+        self.notifying = true;
+        self.notifying_changed(&self.ctxt).await?;
+        Ok(())
+    }
+
+    async fn stop_notify(&mut self) -> std::result::Result<(), MockError> {
+        self.notifying = false;
+        self.notifying_changed(&self.ctxt).await?;
+        Ok(())
+    }
+
+    #[dbus_interface(property, name = "UUID")]
+    fn uuid(&self) -> String {
+        self.uuid.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn service(&self) -> OwnedObjectPath {
+        self.service.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn value(&self) -> Vec<u8> {
+        self.value.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn flags(&self) -> Vec<String> {
+        self.flags.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn notifying(&self) -> bool {
+        self.notifying
+    }
+
+    #[dbus_interface(property, name = "MTU")]
+    fn mtu(&self) -> u16 {
+        23
+    }
+
+    #[dbus_interface(property)]
+    fn notify_acquired(&self) -> bool {
+        self.notify_io.is_some()
+    }
+
+    #[dbus_interface(property)]
+    fn write_acquired(&self) -> bool {
+        self.write_io.is_some()
+    }
+}
+
+/// A handle to a fake GATT characteristic exported by [`MockBluez`].
+pub struct MockCharacteristic {
+    conn: Connection,
+    device: OwnedObjectPath,
+    path: OwnedObjectPath,
+}
+
+impl MockCharacteristic {
+    async fn interface(&self) -> Result<InterfaceRef<CharacteristicMock>> {
+        interface(&self.conn, &self.path).await
+    }
+
+    /// Adds a descriptor with the given [`Uuid`] and BlueZ flags (eg. `read`, `write`).
+    pub async fn add_descriptor(&self, uuid: Uuid, flags: &[&str]) -> Result<MockDescriptor> {
+        let device = interface::<DeviceMock>(&self.conn, &self.device).await?;
+        let path = {
+            let mut device = device.get_mut().await;
+            let path = owned_path(format!(
+                "{}/desc{:04x}",
+                self.path.as_str(),
+                device.next_handle
+            ))?;
+            device.next_handle += 1;
+            device.objects.push((path.clone(), ObjectKind::Descriptor));
+            path
+        };
+        let iface = DescriptorMock {
+            uuid: uuid.to_string(),
+            characteristic: self.path.clone(),
+            flags: flags.iter().map(ToString::to_string).collect(),
+            value: Vec::new(),
+            written: Vec::new(),
+        };
+        add_object(&self.conn, &path, iface).await?;
+
+        Ok(MockDescriptor {
+            conn: self.conn.clone(),
+            path,
+        })
+    }
+
+    /// Sets the value returned when reading the characteristic.
+    pub async fn set_value(&self, value: &[u8]) -> Result<()> {
+        let characteristic = self.interface().await?;
+        characteristic.get_mut().await.value = value.to_vec();
+        Ok(())
+    }
+
+    /// Limits the number of Bytes returned by each `ReadValue` call, like a single ATT Read (Blob)
+    /// Response.
+    ///
+    /// By default, `ReadValue` returns the whole value from the requested offset on, as if BlueZ
+    /// had performed a long read.
+    pub async fn set_max_read_len(&self, len: Option<usize>) -> Result<()> {
+        let characteristic = self.interface().await?;
+        characteristic.get_mut().await.max_read_len = len;
+        Ok(())
+    }
+
+    /// Returns the offsets of the `ReadValue` calls since the last call to this method, in order.
+    pub async fn read_offsets(&self) -> Result<Vec<u16>> {
+        let characteristic = self.interface().await?;
+        let offsets = mem::take(&mut characteristic.get_mut().await.read_offsets);
+        Ok(offsets)
+    }
+
+    /// Sends a notification with the given value, if notifications are enabled (via `StartNotify`
+    /// or `AcquireNotify`).
+    ///
+    /// Returns whether a notification was sent.
+    pub async fn notify(&self, value: &[u8]) -> Result<bool> {
+        let characteristic = self.interface().await?;
+        let mut characteristic = characteristic.get_mut().await;
+        characteristic.value = value.to_vec();
+        characteristic.check_notify_io();
+        if let Some(io) = &characteristic.notify_io {
+            io.send(value).map_err(Error::from)?;
+            return Ok(true);
+        }
+        if !characteristic.notifying {
+            return Ok(false);
+        }
+        characteristic
+            .value_changed(&characteristic.ctxt)
+            .await
+            .map_err(Error::from)?;
+        Ok(true)
+    }
+
+    /// Returns whether notifications are currently enabled.
+    pub async fn is_notifying(&self) -> Result<bool> {
+        let characteristic = self.interface().await?;
+        let notifying = characteristic.get().await.notifying;
+        Ok(notifying)
+    }
+
+    /// Returns whether the notification socket is currently acquired (via `AcquireNotify`).
+    pub async fn is_notify_acquired(&self) -> Result<bool> {
+        let characteristic = self.interface().await?;
+        let mut characteristic = characteristic.get_mut().await;
+        characteristic.check_notify_io();
+        Ok(characteristic.notify_io.is_some())
+    }
+
+    /// Returns whether the write socket is currently acquired (via `AcquireWrite`).
+    pub async fn is_write_acquired(&self) -> Result<bool> {
+        let characteristic = self.interface().await?;
+        let acquired = characteristic.get().await.write_io.is_some();
+        Ok(acquired)
+    }
+
+    /// Returns all values written to the characteristic so far (via `WriteValue` or
+    /// `AcquireWrite`), in order.
+    pub async fn written_values(&self) -> Result<Vec<Vec<u8>>> {
+        let characteristic = self.interface().await?;
+        let mut characteristic = characteristic.get_mut().await;
+        characteristic.drain_write_io();
+        Ok(characteristic.written.clone())
+    }
+}
+
+struct DescriptorMock {
+    uuid: String,
+    characteristic: OwnedObjectPath,
+    flags: Vec<String>,
+    value: Vec<u8>,
+    written: Vec<Vec<u8>>,
+}
+
+#[dbus_interface(name = "org.bluez.GattDescriptor1")]
+impl DescriptorMock {
+    async fn read_value(
+        &self,
+        options: HashMap<String, OwnedValue>,
+    ) -> std::result::Result<Vec<u8>, MockError> {
+        let offset = usize::from(read_offset(&options));
+        if offset > self.value.len() {
+            return Err(MockError::new(
+                "org.bluez.Error.InvalidOffset",
+                "Invalid offset",
+            ));
+        }
+        Ok(self.value[offset..].to_vec())
+    }
+
+    async fn write_value(&mut self, value: Vec<u8>, _options: HashMap<String, OwnedValue>) {
+        self.written.push(value);
+    }
+
+    #[dbus_interface(property, name = "UUID")]
+    fn uuid(&self) -> String {
+        self.uuid.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn characteristic(&self) -> OwnedObjectPath {
+        self.characteristic.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn value(&self) -> Vec<u8> {
+        self.value.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn flags(&self) -> Vec<String> {
+        self.flags.clone()
+    }
+}
+
+/// A handle to a fake GATT descriptor exported by [`MockBluez`].
+pub struct MockDescriptor {
+    conn: Connection,
+    path: OwnedObjectPath,
+}
+
+impl MockDescriptor {
+    async fn interface(&self) -> Result<InterfaceRef<DescriptorMock>> {
+        interface(&self.conn, &self.path).await
+    }
+
+    /// Sets the value returned when reading the descriptor.
+    pub async fn set_value(&self, value: &[u8]) -> Result<()> {
+        let descriptor = self.interface().await?;
+        descriptor.get_mut().await.value = value.to_vec();
+        Ok(())
+    }
+
+    /// Returns all values written to the descriptor so far, in order.
+    pub async fn written_values(&self) -> Result<Vec<Vec<u8>>> {
+        let descriptor = self.interface().await?;
+        let written = descriptor.get().await.written.clone();
+        Ok(written)
+    }
+}
+
+const AGENT_MANAGER_PATH: &str = "/org/bluez";
+
+/// An agent registered via `RegisterAgent`.
+#[derive(Clone)]
+struct RegisteredAgent {
+    /// The unique name of the registering connection ([`None`] for the in-process connection).
+    owner: Option<String>,
+    path: OwnedObjectPath,
+    capability: String,
+    default: bool,
+}
+
+#[derive(Default)]
+struct AgentManagerMock {
+    /// Registered agents, in registration order.
+    agents: Vec<RegisteredAgent>,
+}
+
+impl AgentManagerMock {
+    fn find(
+        &self,
+        header: &MessageHeader<'_>,
+        path: &OwnedObjectPath,
+    ) -> std::result::Result<usize, MockError> {
+        let owner = sender(header)?;
+        self.agents
+            .iter()
+            .position(|agent| agent.owner == owner && agent.path == *path)
+            .ok_or_else(|| MockError::new("org.bluez.Error.DoesNotExist", "Does Not Exist"))
+    }
+}
+
+fn sender(header: &MessageHeader<'_>) -> std::result::Result<Option<String>, MockError> {
+    Ok(header.sender()?.map(ToString::to_string))
+}
+
+#[dbus_interface(name = "org.bluez.AgentManager1")]
+impl AgentManagerMock {
+    async fn register_agent(
+        &mut self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        agent: OwnedObjectPath,
+        capability: String,
+    ) -> std::result::Result<(), MockError> {
+        let owner = sender(&header)?;
+        // Like BlueZ, only allow one agent per connection.
+        if self.agents.iter().any(|agent| agent.owner == owner) {
+            return Err(MockError::new(
+                "org.bluez.Error.AlreadyExists",
+                "Already Exists",
+            ));
+        }
+        let capability = match &*capability {
+            "" => "KeyboardDisplay".to_string(),
+            "DisplayOnly" | "DisplayYesNo" | "KeyboardOnly" | "NoInputNoOutput"
+            | "KeyboardDisplay" => capability,
+            _ => {
+                return Err(MockError::new(
+                    "org.bluez.Error.InvalidArguments",
+                    "Invalid Arguments",
+                ))
+            }
+        };
+        self.agents.push(RegisteredAgent {
+            owner,
+            path: agent,
+            capability,
+            default: false,
+        });
+        Ok(())
+    }
+
+    async fn unregister_agent(
+        &mut self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        agent: OwnedObjectPath,
+    ) -> std::result::Result<(), MockError> {
+        let index = self.find(&header, &agent)?;
+        self.agents.remove(index);
+        Ok(())
+    }
+
+    async fn request_default_agent(
+        &mut self,
+        #[zbus(header)] header: MessageHeader<'_>,
+        agent: OwnedObjectPath,
+    ) -> std::result::Result<(), MockError> {
+        let index = self.find(&header, &agent)?;
+        for (i, agent) in self.agents.iter_mut().enumerate() {
+            agent.default = i == index;
+        }
+        Ok(())
+    }
+}
+
+/// A handle to an agent registered with [`MockBluez`], returned by [`MockBluez::agent`].
+///
+/// This sends requests to the agent's `org.bluez.Agent1` object, like BlueZ does during pairing.
+/// Errors returned by the agent (eg. `org.bluez.Error.Rejected`) are passed through.
+pub struct MockAgent {
+    conn: Connection,
+    agent: RegisteredAgent,
+}
+
+impl MockAgent {
+    /// Returns the capability the agent was registered with (eg. `KeyboardOnly`).
+    pub fn capability(&self) -> &str {
+        &self.agent.capability
+    }
+
+    /// Returns whether the agent was made the default agent.
+    pub fn is_default(&self) -> bool {
+        self.agent.default
+    }
+
+    async fn call<B, R>(&self, method: &str, body: &B) -> Result<R>
+    where
+        B: Serialize + DynamicType,
+        R: DeserializeOwned + Type,
+    {
+        let reply = self
+            .conn
+            .call_method(
+                self.agent.owner.as_deref(),
+                &self.agent.path,
+                Some("org.bluez.Agent1"),
+                method,
+                body,
+            )
+            .await
+            .map_err(Error::from)?;
+        reply.body().map_err(Error::from)
+    }
+
+    /// Requests the passkey to use for pairing with `device`.
+    pub async fn request_passkey(&self, device: &MockDevice) -> Result<u32> {
+        self.call("RequestPasskey", &(&device.path,)).await
+    }
+
+    /// Requests that `passkey` is displayed while the user types `entered` digits on `device`.
+    pub async fn display_passkey(
+        &self,
+        device: &MockDevice,
+        passkey: u32,
+        entered: u16,
+    ) -> Result<()> {
+        self.call("DisplayPasskey", &(&device.path, passkey, entered))
+            .await
+    }
+
+    /// Requests confirmation that `passkey` is displayed by `device`.
+    pub async fn request_confirmation(&self, device: &MockDevice, passkey: u32) -> Result<()> {
+        self.call("RequestConfirmation", &(&device.path, passkey))
+            .await
+    }
+
+    /// Requests authorization for pairing with `device`.
+    pub async fn request_authorization(&self, device: &MockDevice) -> Result<()> {
+        self.call("RequestAuthorization", &(&device.path,)).await
+    }
+
+    /// Cancels the agent's outstanding request.
+    pub async fn cancel(&self) -> Result<()> {
+        self.call("Cancel", &()).await
+    }
+}
@@ -0,0 +1,1041 @@
+//! Integration tests running against the mock BlueZ from the `testing` module.
+
+// The tests drive the mock with `pollster`, while the `tokio` feature requires a tokio runtime.
+#![cfg(all(feature = "testing", not(feature = "tokio")))]
+
+use std::{
+    collections::HashMap,
+    pin::pin,
+    process::Command,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures_util::{future::join, poll};
+
+use blues::{
+    address::{Address, AddressType},
+    agent::{Agent, AgentError, AgentHandle, AgentOptions, AutoAccept, FixedPasskey},
+    blocking,
+    device::{Device, PropertyChange, PropertyName},
+    gatt::{PresentationFormat, ReadOptions},
+    testing::MockBluez,
+    uuid::Uuid,
+    Adapter, AdapterEvent, AdapterStream, DeviceSetChange, DiscoveryFilter, ErrorKind, PowerState,
+    Session, Transport,
+};
+
+const ADAPTER: &str = "00:11:22:33:44:55";
+const DEVICE: &str = "AA:BB:CC:DD:EE:FF";
+const SERVICE: Uuid = Uuid::from_u16(0x180d);
+const CHARACTERISTIC: Uuid = Uuid::from_u16(0x2a37);
+
+#[test]
+fn enumerate_adapters() {
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        mock.add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        mock.add_adapter("hci1", "00:11:22:33:44:66".parse().unwrap())
+            .await
+            .unwrap();
+        let session = mock.session().await.unwrap();
+
+        let mut names = Adapter::enumerate(&session)
+            .await
+            .unwrap()
+            .map(|adapter| adapter.device_name().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["hci0", "hci1"]);
+
+        let adapter = Adapter::open_by_address(&session, ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(adapter.device_name(), "hci0");
+        assert!(Adapter::open_by_name(&session, "hci2").await.is_err());
+    });
+}
+
+#[test]
+fn custom_service_name() {
+    // Service names only exist on a bus.
+    if Command::new("dbus-daemon")
+        .arg("--version")
+        .output()
+        .is_err()
+    {
+        eprintln!("skipping test: `dbus-daemon` is not installed");
+        return;
+    }
+
+    pollster::block_on(async {
+        let mock = MockBluez::with_bus().await.unwrap();
+        mock.add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let address = mock.address().unwrap();
+
+        let session = Session::builder()
+            .address(address)
+            .service_name("org.bluez")
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(Adapter::enumerate(&session).await.unwrap().count(), 1);
+
+        let session = Session::builder()
+            .address(address)
+            .service_name("org.example.bluez")
+            .build()
+            .await
+            .unwrap();
+        let err = Adapter::enumerate(&session).await.err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::DoesNotExist);
+
+        assert!(Session::builder()
+            .address(address)
+            .service_name("not a bus name")
+            .build()
+            .await
+            .is_err());
+    });
+}
+
+#[test]
+fn hotplug_stream_reenumerates_on_restart() {
+    // Restarts are detected via the owner of the service name, which only exists on a bus.
+    if Command::new("dbus-daemon")
+        .arg("--version")
+        .output()
+        .is_err()
+    {
+        eprintln!("skipping test: `dbus-daemon` is not installed");
+        return;
+    }
+
+    async fn added(stream: &mut AdapterStream) -> String {
+        match stream.next().await.unwrap() {
+            AdapterEvent::Added(adapter) => adapter.device_name().to_string(),
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    async fn removed(stream: &mut AdapterStream) -> String {
+        match stream.next().await.unwrap() {
+            AdapterEvent::Removed(name) => name,
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    pollster::block_on(async {
+        let mock = MockBluez::with_bus().await.unwrap();
+        mock.add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let session = mock.session().await.unwrap();
+        let mut stream = Adapter::hotplug_stream(&session).await.unwrap();
+        assert_eq!(added(&mut stream).await, "hci0");
+
+        let mock_adapter = mock
+            .add_adapter("hci1", "00:11:22:33:44:66".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(added(&mut stream).await, "hci1");
+        mock_adapter.remove().await.unwrap();
+        assert_eq!(removed(&mut stream).await, "hci1");
+
+        mock.stop_service().await.unwrap();
+        assert_eq!(removed(&mut stream).await, "hci0");
+
+        // Adapters added while BlueZ is gone are only found by reenumerating.
+        mock.add_adapter("hci2", "00:11:22:33:44:77".parse().unwrap())
+            .await
+            .unwrap();
+        mock.start_service().await.unwrap();
+        let mut names = [added(&mut stream).await, added(&mut stream).await];
+        names.sort();
+        assert_eq!(names, ["hci0", "hci2"]);
+    });
+}
+
+#[test]
+fn power_and_discovery() {
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let session = mock.session().await.unwrap();
+        let adapter = Adapter::open(&session).await.unwrap();
+
+        adapter.set_powered(false).await.unwrap();
+        assert!(!mock_adapter.is_powered().await.unwrap());
+        let err = adapter.start_discovery().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotReady);
+
+        // Reopen the adapter, since property changes are delivered asynchronously.
+        let adapter = Adapter::open(&session).await.unwrap();
+        assert_eq!(adapter.power_state().await.unwrap(), PowerState::Off);
+        adapter.ensure_powered().await.unwrap();
+        assert!(mock_adapter.is_powered().await.unwrap());
+
+        let guard = adapter.discover().await.unwrap();
+        assert!(mock_adapter.is_discovering().await.unwrap());
+        guard.stop().await.unwrap();
+        assert!(!mock_adapter.is_discovering().await.unwrap());
+    });
+}
+
+#[test]
+fn discovery_is_shared() {
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let session = mock.session().await.unwrap();
+        let adapter = Adapter::open(&session).await.unwrap();
+
+        // The second caller waits for the first `StartDiscovery` instead of issuing its own (which
+        // would fail).
+        let (first, second) = join(adapter.discover(), adapter.discover()).await;
+        first.unwrap().stop().await.unwrap();
+        assert!(mock_adapter.is_discovering().await.unwrap());
+        second.unwrap().stop().await.unwrap();
+        assert!(!mock_adapter.is_discovering().await.unwrap());
+
+        // Cancelling `discover` must not leave discovery running, or the reference count behind.
+        {
+            let mut discover = pin!(adapter.discover());
+            assert!(poll!(discover.as_mut()).is_pending());
+        }
+        let guard = adapter.discover().await.unwrap();
+        assert!(mock_adapter.is_discovering().await.unwrap());
+        guard.stop().await.unwrap();
+        assert!(!mock_adapter.is_discovering().await.unwrap());
+    });
+}
+
+#[test]
+fn discovery_filter() {
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let session = mock.session().await.unwrap();
+        let adapter = Adapter::open(&session).await.unwrap();
+        assert!(adapter
+            .discovery_filters()
+            .await
+            .unwrap()
+            .iter()
+            .any(|name| name == "UUIDs"));
+
+        let filter = DiscoveryFilter::new()
+            .uuids([SERVICE])
+            .min_rssi(-70)
+            .transport(Transport::Le)
+            .duplicate_data(true)
+            .pattern("AA:BB");
+        let guard = adapter.discover_with(&filter).await.unwrap();
+        assert!(mock_adapter.is_discovering().await.unwrap());
+        let set = mock_adapter.discovery_filter().await.unwrap();
+        assert_eq!(set.uuids, [SERVICE]);
+        assert_eq!(set.rssi, Some(-70));
+        assert_eq!(set.pathloss, None);
+        assert_eq!(set.transport.as_deref(), Some("le"));
+        assert_eq!(set.duplicate_data, Some(true));
+        assert_eq!(set.discoverable, None);
+        assert_eq!(set.pattern.as_deref(), Some("AA:BB"));
+        guard.stop().await.unwrap();
+
+        // An empty filter clears the previous one.
+        adapter
+            .set_discovery_filter(&DiscoveryFilter::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            mock_adapter.discovery_filter().await.unwrap(),
+            Default::default()
+        );
+
+        let filter = DiscoveryFilter::new().min_rssi(-70).max_pathloss(20);
+        let err = adapter.discover_with(&filter).await.err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidArguments);
+        assert!(!mock_adapter.is_discovering().await.unwrap());
+    });
+}
+
+#[test]
+fn device_set_reports_advertisements() {
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let session = mock.session().await.unwrap();
+        let adapter = Adapter::open(&session).await.unwrap();
+
+        let mut set = adapter
+            .device_set([PropertyName::Rssi, PropertyName::ManufacturerData])
+            .await
+            .unwrap();
+        assert!(set.devices().is_empty());
+
+        let mock_device = mock_adapter
+            .add_device(DEVICE.parse().unwrap())
+            .await
+            .unwrap();
+        match set.change().await.unwrap() {
+            DeviceSetChange::Added(device) => {
+                assert_eq!(device.address().await.unwrap(), DEVICE.parse().unwrap());
+            }
+            change => panic!("unexpected change: {:?}", change),
+        }
+
+        mock_device.set_rssi(-42).await.unwrap();
+        match set.change().await.unwrap() {
+            DeviceSetChange::Changed(device, change) => {
+                assert_eq!(device.address().await.unwrap(), DEVICE.parse().unwrap());
+                assert_eq!(change, PropertyChange::Rssi(-42));
+            }
+            change => panic!("unexpected change: {:?}", change),
+        }
+
+        mock_device
+            .set_manufacturer_data(0x004c, &[1, 2, 3])
+            .await
+            .unwrap();
+        match set.change().await.unwrap() {
+            DeviceSetChange::Changed(_, PropertyChange::ManufacturerData(data)) => {
+                assert_eq!(data.get(&0x004c).map(|v| &**v), Some(&[1, 2, 3][..]));
+            }
+            change => panic!("unexpected change: {:?}", change),
+        }
+
+        mock_device.remove().await.unwrap();
+        match set.change().await.unwrap() {
+            DeviceSetChange::Removed(_) => {}
+            change => panic!("unexpected change: {:?}", change),
+        }
+    });
+}
+
+#[test]
+fn advertisement_data() {
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let mock_device = mock_adapter
+            .add_device(DEVICE.parse().unwrap())
+            .await
+            .unwrap();
+        mock_device
+            .set_manufacturer_data(0x004c, &[1, 2, 3])
+            .await
+            .unwrap();
+        mock_device
+            .set_service_data(SERVICE, &[4, 5])
+            .await
+            .unwrap();
+        let session = mock.session().await.unwrap();
+        let adapter = Adapter::open(&session).await.unwrap();
+        let device = adapter
+            .device(DEVICE.parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let data = device.manufacturer_data().await.unwrap();
+        assert_eq!(data, HashMap::from([(0x004c, vec![1, 2, 3])]));
+        let data = device.service_data().await.unwrap();
+        assert_eq!(data, HashMap::from([(SERVICE, vec![4, 5])]));
+    });
+}
+
+#[test]
+fn connect_retries_transient_failures() {
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let mock_device = mock_adapter
+            .add_device(DEVICE.parse().unwrap())
+            .await
+            .unwrap();
+        let session = mock.session().await.unwrap();
+        let adapter = Adapter::open(&session).await.unwrap();
+        let device = adapter
+            .device(DEVICE.parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+
+        mock_device
+            .fail_next_connect("org.bluez.Error.NotReady", "Resource Not Ready")
+            .await
+            .unwrap();
+        let err = device.connect().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotReady);
+        assert!(!mock_device.is_connected().await.unwrap());
+
+        mock_device
+            .fail_next_connect("org.bluez.Error.Failed", "le-connection-abort-by-local")
+            .await
+            .unwrap();
+        let start = Instant::now();
+        device.connect().await.unwrap();
+        assert!(mock_device.is_connected().await.unwrap());
+        // The retry is delayed to give the controller some time to recover.
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    });
+}
+
+#[test]
+fn connect_device() {
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let session = mock.session().await.unwrap();
+        let adapter = Adapter::open(&session).await.unwrap();
+
+        // An unknown device is created and connected by `ConnectDevice`.
+        let address: Address = "AA:BB:CC:DD:EE:00".parse().unwrap();
+        assert!(adapter.device(address).await.unwrap().is_none());
+        let device = adapter
+            .connect_device(address, AddressType::Random)
+            .await
+            .unwrap();
+        assert_eq!(device.address().await.unwrap(), address);
+        assert_eq!(device.address_type().await.unwrap(), AddressType::Random);
+        assert!(device.is_connected().await.unwrap());
+
+        // For a known device, `ConnectDevice` fails with `AlreadyExists`, so `Connect` is used.
+        let mock_device = mock_adapter
+            .add_device(DEVICE.parse().unwrap())
+            .await
+            .unwrap();
+        let device = adapter
+            .connect_device(DEVICE.parse().unwrap(), AddressType::Public)
+            .await
+            .unwrap();
+        assert_eq!(device.address().await.unwrap(), DEVICE.parse().unwrap());
+        assert!(mock_device.is_connected().await.unwrap());
+
+        // Errors from `Connect` are passed through.
+        mock_device.disconnect().await.unwrap();
+        mock_device
+            .fail_next_connect("org.bluez.Error.NotReady", "Resource Not Ready")
+            .await
+            .unwrap();
+        let err = adapter
+            .connect_device(DEVICE.parse().unwrap(), AddressType::Public)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotReady);
+        assert!(!mock_device.is_connected().await.unwrap());
+    });
+}
+
+#[test]
+fn remove_device() {
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let mock_device = mock_adapter
+            .add_device(DEVICE.parse().unwrap())
+            .await
+            .unwrap();
+        mock_device.add_service(SERVICE).await.unwrap();
+        let session = mock.session().await.unwrap();
+        let adapter = Adapter::open(&session).await.unwrap();
+        let device = adapter
+            .device(DEVICE.parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+
+        // The device is gone once the call returns.
+        adapter.remove_device(&device).await.unwrap();
+        assert!(mock_device.is_connected().await.is_err());
+        assert!(adapter
+            .device(DEVICE.parse().unwrap())
+            .await
+            .unwrap()
+            .is_none());
+    });
+}
+
+#[test]
+fn device_pairing() {
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let mock_device = mock_adapter
+            .add_device(DEVICE.parse().unwrap())
+            .await
+            .unwrap();
+        let session = mock.session().await.unwrap();
+        let adapter = Adapter::open(&session).await.unwrap();
+        let device = adapter
+            .device(DEVICE.parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(!device.is_paired().await.unwrap());
+        assert!(!device.is_bonded().await.unwrap());
+        assert!(!device.is_legacy_pairing().await.unwrap());
+        device.pair().await.unwrap();
+        assert!(mock_device.is_paired().await.unwrap());
+        assert!(device.is_paired().await.unwrap());
+        assert!(device.is_bonded().await.unwrap());
+        // Pairing an already paired device is not an error.
+        device.pair().await.unwrap();
+        let err = device.cancel_pairing().await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::DoesNotExist);
+
+        assert!(!device.is_trusted().await.unwrap());
+        device.set_trusted(true).await.unwrap();
+        assert!(device.is_trusted().await.unwrap());
+
+        assert!(!device.is_wake_allowed().await.unwrap());
+        device.set_wake_allowed(true).await.unwrap();
+        assert!(device.is_wake_allowed().await.unwrap());
+
+        device.connect().await.unwrap();
+        device.set_blocked(true).await.unwrap();
+        assert!(device.is_blocked().await.unwrap());
+        assert!(!mock_device.is_connected().await.unwrap());
+        device.set_blocked(false).await.unwrap();
+        assert!(!device.is_blocked().await.unwrap());
+    });
+}
+
+#[test]
+fn agents() {
+    /// Records the passkeys it is asked to display.
+    #[derive(Default)]
+    struct Display(Arc<Mutex<Vec<(u32, u16)>>>);
+
+    impl Agent for Display {
+        async fn display_passkey(
+            &self,
+            _: &Device,
+            passkey: u32,
+            entered: u16,
+        ) -> Result<(), AgentError> {
+            self.0.lock().unwrap().push((passkey, entered));
+            Ok(())
+        }
+    }
+
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let mock_device = mock_adapter
+            .add_device(DEVICE.parse().unwrap())
+            .await
+            .unwrap();
+        let session = mock.session().await.unwrap();
+        assert!(mock.agent().await.unwrap().is_none());
+
+        let handle = AgentHandle::register(&session, FixedPasskey::new(123456))
+            .await
+            .unwrap();
+        let agent = mock.agent().await.unwrap().unwrap();
+        assert_eq!(agent.capability(), "KeyboardOnly");
+        assert!(!agent.is_default());
+        assert_eq!(agent.request_passkey(&mock_device).await.unwrap(), 123456);
+        agent
+            .request_confirmation(&mock_device, 123456)
+            .await
+            .unwrap();
+        let err = agent
+            .request_confirmation(&mock_device, 654321)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Rejected);
+        agent.cancel().await.unwrap();
+
+        handle.unregister().await.unwrap();
+        assert!(mock.agent().await.unwrap().is_none());
+        let err = agent.request_passkey(&mock_device).await.unwrap_err();
+        assert_eq!(
+            err.dbus_name(),
+            Some("org.freedesktop.DBus.Error.UnknownObject")
+        );
+
+        let options = AgentOptions::new().default_agent(true);
+        let handle = AgentHandle::register_with(&session, AutoAccept, &options)
+            .await
+            .unwrap();
+        let agent = mock.agent().await.unwrap().unwrap();
+        assert_eq!(agent.capability(), "NoInputNoOutput");
+        assert!(agent.is_default());
+        agent.request_confirmation(&mock_device, 1).await.unwrap();
+        agent.request_authorization(&mock_device).await.unwrap();
+        let err = agent.request_passkey(&mock_device).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Rejected);
+
+        // Dropping the handle unregisters the agent in the background.
+        drop(handle);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while mock.agent().await.unwrap().is_some() {
+            assert!(Instant::now() < deadline, "agent was not unregistered");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let display = Display::default();
+        let displayed = display.0.clone();
+        let _handle = AgentHandle::register(&session, display).await.unwrap();
+        let agent = mock.agent().await.unwrap().unwrap();
+        assert_eq!(agent.capability(), "KeyboardDisplay");
+        agent.display_passkey(&mock_device, 42, 3).await.unwrap();
+        assert_eq!(*displayed.lock().unwrap(), [(42, 3)]);
+    });
+}
+
+#[test]
+fn gatt_read_write_notify() {
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let mock_device = mock_adapter
+            .add_device(DEVICE.parse().unwrap())
+            .await
+            .unwrap();
+        let mock_service = mock_device.add_service(SERVICE).await.unwrap();
+        let mock_char = mock_service
+            .add_characteristic(CHARACTERISTIC, &["read", "write", "notify"])
+            .await
+            .unwrap();
+        mock_char.set_value(&[0x00, 0x48]).await.unwrap();
+
+        let session = mock.session().await.unwrap();
+        let adapter = Adapter::open(&session).await.unwrap();
+        let device = adapter
+            .device(DEVICE.parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        device.connect().await.unwrap();
+
+        let services = device.gatt_services().await.unwrap();
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].uuid().await.unwrap(), SERVICE);
+        let characteristic = services[0].characteristic(CHARACTERISTIC).await.unwrap();
+
+        assert_eq!(characteristic.read().await.unwrap(), [0x00, 0x48]);
+        characteristic.write(&[0x01]).await.unwrap();
+        assert_eq!(mock_char.written_values().await.unwrap(), [vec![0x01]]);
+
+        let mut values = characteristic.subscribe().await.unwrap();
+        assert!(mock_char.is_notifying().await.unwrap());
+        assert!(mock_char.notify(&[0x00, 0x50]).await.unwrap());
+        assert_eq!(values.next().await.unwrap(), [0x00, 0x50]);
+
+        mock_device.disconnect().await.unwrap();
+        assert!(values.next().await.unwrap_err().is_disconnected());
+    });
+}
+
+#[test]
+fn gatt_descriptors() {
+    const USER_DESCRIPTION: Uuid = Uuid::from_u16(0x2901);
+    const VENDOR: Uuid = Uuid::from_static("9d2e0001-4f3a-4c39-8f51-2ef8d3a6c0de");
+
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let mock_device = mock_adapter
+            .add_device(DEVICE.parse().unwrap())
+            .await
+            .unwrap();
+        let mock_service = mock_device.add_service(SERVICE).await.unwrap();
+        let mock_char = mock_service
+            .add_characteristic(CHARACTERISTIC, &["read"])
+            .await
+            .unwrap();
+        let mock_desc = mock_char
+            .add_descriptor(USER_DESCRIPTION, &["read"])
+            .await
+            .unwrap();
+        mock_desc.set_value(b"Heart Rate").await.unwrap();
+        let mock_desc = mock_char
+            .add_descriptor(PresentationFormat::UUID, &["read"])
+            .await
+            .unwrap();
+        // uint16, exponent -1, unit 0x27ad (beats per minute).
+        mock_desc
+            .set_value(&[0x06, 0xff, 0xad, 0x27, 0x01, 0x00, 0x00])
+            .await
+            .unwrap();
+        let mock_vendor = mock_char
+            .add_descriptor(VENDOR, &["read", "write"])
+            .await
+            .unwrap();
+        // Descriptors must only be reported for the characteristic they belong to.
+        mock_service
+            .add_characteristic(Uuid::from_u16(0x2a38), &["read"])
+            .await
+            .unwrap();
+
+        let session = mock.session().await.unwrap();
+        let adapter = Adapter::open(&session).await.unwrap();
+        let device = adapter
+            .device(DEVICE.parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        device.connect().await.unwrap();
+        let services = device.gatt_services().await.unwrap();
+        let characteristic = services[0].characteristic(CHARACTERISTIC).await.unwrap();
+
+        let mut uuids = Vec::new();
+        for descriptor in characteristic.descriptors().await.unwrap() {
+            uuids.push(descriptor.uuid().await.unwrap());
+        }
+        assert_eq!(uuids.len(), 3);
+        for uuid in [USER_DESCRIPTION, PresentationFormat::UUID, VENDOR] {
+            assert!(uuids.contains(&uuid), "missing descriptor {}", uuid);
+        }
+
+        let descriptor = characteristic.descriptor(USER_DESCRIPTION).await.unwrap();
+        assert_eq!(descriptor.read().await.unwrap(), b"Heart Rate");
+        let flags = descriptor.flags().await.unwrap();
+        assert!(flags.can_read());
+        assert!(!flags.can_write());
+
+        let format = characteristic.presentation_format().await.unwrap();
+        assert_eq!(format.exponent(), -1);
+        assert_eq!(format.unit(), Uuid::from_u16(0x27ad));
+
+        let descriptor = characteristic.descriptor(VENDOR).await.unwrap();
+        assert!(descriptor.flags().await.unwrap().can_write());
+        descriptor.write(&[0x01, 0x02]).await.unwrap();
+        assert_eq!(
+            mock_vendor.written_values().await.unwrap(),
+            [vec![0x01, 0x02]]
+        );
+        assert!(characteristic
+            .descriptor(Uuid::from_u16(0x2902))
+            .await
+            .is_err());
+
+        let other = services[0]
+            .characteristic(Uuid::from_u16(0x2a38))
+            .await
+            .unwrap();
+        assert!(other.descriptors().await.unwrap().is_empty());
+    });
+}
+
+#[test]
+fn gatt_long_read() {
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let mock_device = mock_adapter
+            .add_device(DEVICE.parse().unwrap())
+            .await
+            .unwrap();
+        let mock_service = mock_device.add_service(SERVICE).await.unwrap();
+        let mock_char = mock_service
+            .add_characteristic(CHARACTERISTIC, &["read"])
+            .await
+            .unwrap();
+
+        let session = mock.session().await.unwrap();
+        let adapter = Adapter::open(&session).await.unwrap();
+        let device = adapter
+            .device(DEVICE.parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        device.connect().await.unwrap();
+        let services = device.gatt_services().await.unwrap();
+        let characteristic = services[0].characteristic(CHARACTERISTIC).await.unwrap();
+
+        // The mock reports an MTU of 23, so every response carries at most 22 Bytes.
+        let value = (0..=255).cycle().take(600).collect::<Vec<u8>>();
+        let long = ReadOptions::new().read_long(true);
+        mock_char.set_max_read_len(Some(22)).await.unwrap();
+
+        // A single read only returns the first chunk.
+        mock_char.set_value(&value[..50]).await.unwrap();
+        assert_eq!(characteristic.read().await.unwrap(), value[..22]);
+        assert_eq!(mock_char.read_offsets().await.unwrap(), [0]);
+
+        // A short chunk ends the long read.
+        assert_eq!(characteristic.read_with(&long).await.unwrap(), value[..50]);
+        assert_eq!(mock_char.read_offsets().await.unwrap(), [0, 22, 44]);
+
+        // If the value is a multiple of the chunk size, an empty chunk ends the long read.
+        mock_char.set_value(&value[..44]).await.unwrap();
+        assert_eq!(characteristic.read_with(&long).await.unwrap(), value[..44]);
+        assert_eq!(mock_char.read_offsets().await.unwrap(), [0, 22, 44]);
+
+        // The long read stops once the maximum attribute length of 512 Bytes is reached.
+        mock_char.set_value(&value).await.unwrap();
+        let read = characteristic.read_with(&long).await.unwrap();
+        assert_eq!(read, value[..528]);
+        let offsets = (0..24).map(|i| i * 22).collect::<Vec<u16>>();
+        assert_eq!(mock_char.read_offsets().await.unwrap(), offsets);
+
+        // The chunk size is derived from an explicit MTU, and reading starts at the given offset.
+        mock_char.set_max_read_len(Some(32)).await.unwrap();
+        mock_char.set_value(&value[..50]).await.unwrap();
+        let options = ReadOptions::new().read_long(true).mtu(33).offset(10);
+        assert_eq!(
+            characteristic.read_with(&options).await.unwrap(),
+            value[10..50]
+        );
+        assert_eq!(mock_char.read_offsets().await.unwrap(), [10, 42]);
+
+        // If BlueZ returns more than a single chunk, it has already read the rest of the value.
+        mock_char.set_max_read_len(None).await.unwrap();
+        assert_eq!(characteristic.read_with(&long).await.unwrap(), value[..50]);
+        assert_eq!(mock_char.read_offsets().await.unwrap(), [0]);
+    });
+}
+
+#[test]
+fn gatt_acquire() {
+    pollster::block_on(async {
+        let mock = MockBluez::new().await.unwrap();
+        let mock_adapter = mock
+            .add_adapter("hci0", ADAPTER.parse().unwrap())
+            .await
+            .unwrap();
+        let mock_device = mock_adapter
+            .add_device(DEVICE.parse().unwrap())
+            .await
+            .unwrap();
+        let mock_service = mock_device.add_service(SERVICE).await.unwrap();
+        let mock_fast = mock_service
+            .add_characteristic(CHARACTERISTIC, &["notify", "write-without-response"])
+            .await
+            .unwrap();
+        let mock_slow = mock_service
+            .add_characteristic(Uuid::from_u16(0x2a39), &["write"])
+            .await
+            .unwrap();
+
+        let session = mock.session().await.unwrap();
+        let adapter = Adapter::open(&session).await.unwrap();
+        let device = adapter
+            .device(DEVICE.parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        device.connect().await.unwrap();
+        let services = device.gatt_services().await.unwrap();
+        let fast = services[0].characteristic(CHARACTERISTIC).await.unwrap();
+        let slow = services[0]
+            .characteristic(Uuid::from_u16(0x2a39))
+            .await
+            .unwrap();
+
+        // Both go through the acquired sockets.
+        let mut writer = fast.acquire_write().await.unwrap();
+        assert!(mock_fast.is_write_acquired().await.unwrap());
+        writer.write(&[0x01, 0x02]).await.unwrap();
+        assert_eq!(
+            mock_fast.written_values().await.unwrap(),
+            [vec![0x01, 0x02]]
+        );
+        let mut values = fast.acquire_notify().await.unwrap();
+        assert!(mock_fast.is_notify_acquired().await.unwrap());
+        assert!(mock_fast.notify(&[]).await.unwrap());
+        assert!(mock_fast.notify(&[0x03]).await.unwrap());
+        assert_eq!(values.next().await.unwrap(), []);
+        assert_eq!(values.next().await.unwrap(), [0x03]);
+        assert!(!mock_fast.is_notifying().await.unwrap());
+
+        // While notifications are enabled via `StartNotify`, `AcquireNotify` fails, and the
+        // subscription is shared instead.
+        drop(values);
+        let mut values = fast.subscribe().await.unwrap();
+        let mut fallback = fast.acquire_notify().await.unwrap();
+        assert!(!mock_fast.is_notify_acquired().await.unwrap());
+        assert!(mock_fast.notify(&[0x04]).await.unwrap());
+        assert_eq!(values.next().await.unwrap(), [0x04]);
+        assert_eq!(fallback.next().await.unwrap(), [0x04]);
+        values.unsubscribe().await.unwrap();
+        fallback.unsubscribe().await.unwrap();
+        assert!(!mock_fast.is_notifying().await.unwrap());
+
+        // Without write-without-response support, `AcquireWrite` fails and `WriteValue` is used.
+        let mut writer = slow.acquire_write().await.unwrap();
+        assert!(!mock_slow.is_write_acquired().await.unwrap());
+        writer.write(&[0x05]).await.unwrap();
+        assert_eq!(mock_slow.written_values().await.unwrap(), [vec![0x05]]);
+
+        // BlueZ closes the socket when the device disconnects.
+        let mut values = fast.acquire_notify().await.unwrap();
+        assert!(mock_fast.notify(&[0x06]).await.unwrap());
+        assert_eq!(values.next().await.unwrap(), [0x06]);
+        mock_device.disconnect().await.unwrap();
+        assert!(values.next().await.unwrap_err().is_disconnected());
+        assert!(!mock_fast.is_notify_acquired().await.unwrap());
+    });
+}
+
+#[test]
+fn blocking_api() {
+    let mock = pollster::block_on(MockBluez::new()).unwrap();
+    let mock_adapter =
+        pollster::block_on(mock.add_adapter("hci0", ADAPTER.parse().unwrap())).unwrap();
+    let mock_device = pollster::block_on(mock_adapter.add_device(DEVICE.parse().unwrap())).unwrap();
+    let mock_service = pollster::block_on(mock_device.add_service(SERVICE)).unwrap();
+    let mock_char = pollster::block_on(
+        mock_service.add_characteristic(CHARACTERISTIC, &["read", "write", "notify"]),
+    )
+    .unwrap();
+    pollster::block_on(mock_char.set_value(&[0x00, 0x48])).unwrap();
+
+    let session = blocking::Session::from(pollster::block_on(mock.session()).unwrap());
+    let adapter = blocking::Adapter::open(&session).unwrap();
+    assert_eq!(adapter.device_name(), "hci0");
+    assert_eq!(adapter.address().unwrap(), ADAPTER.parse().unwrap());
+
+    let device = adapter.device(DEVICE.parse().unwrap()).unwrap().unwrap();
+    device.connect().unwrap();
+    assert!(pollster::block_on(mock_device.is_connected()).unwrap());
+
+    let services = device.gatt_services().unwrap();
+    assert_eq!(services.len(), 1);
+    let characteristic = services[0].characteristic(CHARACTERISTIC).unwrap();
+    assert_eq!(characteristic.read().unwrap(), [0x00, 0x48]);
+    characteristic.write(&[0x01]).unwrap();
+    assert_eq!(
+        pollster::block_on(mock_char.written_values()).unwrap(),
+        [vec![0x01]]
+    );
+
+    let mut values = characteristic.subscribe().unwrap();
+    values.set_timeout(Some(Duration::from_millis(50)));
+    assert!(values.next().is_none());
+    assert!(pollster::block_on(mock_char.notify(&[0x00, 0x50])).unwrap());
+    values.set_timeout(None);
+    assert_eq!(values.next().unwrap().unwrap(), [0x00, 0x50]);
+    values.unsubscribe().unwrap();
+    assert!(!pollster::block_on(mock_char.is_notifying()).unwrap());
+}
+
+#[test]
+fn blocking_device_set() {
+    let mock = pollster::block_on(MockBluez::new()).unwrap();
+    let mock_adapter =
+        pollster::block_on(mock.add_adapter("hci0", ADAPTER.parse().unwrap())).unwrap();
+    let mock_device = pollster::block_on(mock_adapter.add_device(DEVICE.parse().unwrap())).unwrap();
+
+    let session = blocking::Session::from(pollster::block_on(mock.session()).unwrap());
+    let adapter = blocking::Adapter::open(&session).unwrap();
+    let mut set = adapter.device_set([PropertyName::Rssi]).unwrap();
+    set.set_timeout(Some(Duration::from_secs(5)));
+    assert_eq!(set.devices().len(), 1);
+    assert_eq!(set.devices()[0].address().unwrap(), DEVICE.parse().unwrap());
+
+    let other = "AA:BB:CC:DD:EE:00";
+    let mock_other = pollster::block_on(mock_adapter.add_device(other.parse().unwrap())).unwrap();
+    match set.next().unwrap().unwrap() {
+        blocking::DeviceSetChange::Added(device) => {
+            assert_eq!(device.address().unwrap(), other.parse().unwrap());
+        }
+        change => panic!("unexpected change: {:?}", change),
+    }
+    assert_eq!(set.devices().len(), 2);
+
+    pollster::block_on(mock_device.set_rssi(-42)).unwrap();
+    match set.next().unwrap().unwrap() {
+        blocking::DeviceSetChange::Changed(device, change) => {
+            assert_eq!(device.address().unwrap(), DEVICE.parse().unwrap());
+            assert_eq!(change, PropertyChange::Rssi(-42));
+        }
+        change => panic!("unexpected change: {:?}", change),
+    }
+
+    pollster::block_on(mock_device.remove()).unwrap();
+    match set.next().unwrap().unwrap() {
+        blocking::DeviceSetChange::Removed(_) => {}
+        change => panic!("unexpected change: {:?}", change),
+    }
+    assert_eq!(set.devices().len(), 1);
+    assert_eq!(set.devices()[0].address().unwrap(), other.parse().unwrap());
+
+    pollster::block_on(mock_other.remove()).unwrap();
+    assert!(matches!(
+        set.next().unwrap().unwrap(),
+        blocking::DeviceSetChange::Removed(_)
+    ));
+    assert!(set.devices().is_empty());
+}
+
+#[test]
+fn blocking_stream_ends_after_disconnect() {
+    let mock = pollster::block_on(MockBluez::new()).unwrap();
+    let mock_adapter =
+        pollster::block_on(mock.add_adapter("hci0", ADAPTER.parse().unwrap())).unwrap();
+    let mock_device = pollster::block_on(mock_adapter.add_device(DEVICE.parse().unwrap())).unwrap();
+    let mock_service = pollster::block_on(mock_device.add_service(SERVICE)).unwrap();
+    pollster::block_on(mock_service.add_characteristic(CHARACTERISTIC, &["notify"])).unwrap();
+
+    let session = blocking::Session::from(pollster::block_on(mock.session()).unwrap());
+    let adapter = blocking::Adapter::open(&session).unwrap();
+    let device = adapter.device(DEVICE.parse().unwrap()).unwrap().unwrap();
+    device.connect().unwrap();
+    let services = device.gatt_services().unwrap();
+    let characteristic = services[0].characteristic(CHARACTERISTIC).unwrap();
+    let mut values = characteristic.subscribe().unwrap();
+
+    // No timeout is set, so a stream that doesn't end after the error would block forever.
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let first = values.next();
+        let second = values.next();
+        sender.send((first, second)).ok();
+    });
+    pollster::block_on(mock_device.disconnect()).unwrap();
+
+    let (first, second) = receiver
+        .recv_timeout(Duration::from_secs(5))
+        .expect("stream did not end");
+    assert!(first.unwrap().unwrap_err().is_disconnected());
+    assert!(second.is_none());
+}